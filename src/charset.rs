@@ -0,0 +1,658 @@
+use regex_syntax::hir::{Class, HirKind};
+use regex_syntax::ParserBuilder;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+// Every Unicode block as of Unicode 15.1, apart from the surrogate blocks no
+// `char` can fall in. General categories and scripts come from the Unicode
+// tables instead, and win over a block of the same name such as "Arabic".
+const NAMED_BLOCKS: &[(&str, RangeInclusive<char>)] = &[
+    ("Basic Latin", '\u{0000}'..='\u{007F}'),
+    ("Latin-1 Supplement", '\u{0080}'..='\u{00FF}'),
+    ("Latin Extended-A", '\u{0100}'..='\u{017F}'),
+    ("Latin Extended-B", '\u{0180}'..='\u{024F}'),
+    ("IPA Extensions", '\u{0250}'..='\u{02AF}'),
+    ("Spacing Modifier Letters", '\u{02B0}'..='\u{02FF}'),
+    ("Combining Diacritical Marks", '\u{0300}'..='\u{036F}'),
+    ("Greek and Coptic", '\u{0370}'..='\u{03FF}'),
+    ("Cyrillic", '\u{0400}'..='\u{04FF}'),
+    ("Cyrillic Supplement", '\u{0500}'..='\u{052F}'),
+    ("Armenian", '\u{0530}'..='\u{058F}'),
+    ("Hebrew", '\u{0590}'..='\u{05FF}'),
+    ("Arabic", '\u{0600}'..='\u{06FF}'),
+    ("Syriac", '\u{0700}'..='\u{074F}'),
+    ("Arabic Supplement", '\u{0750}'..='\u{077F}'),
+    ("Thaana", '\u{0780}'..='\u{07BF}'),
+    ("NKo", '\u{07C0}'..='\u{07FF}'),
+    ("Samaritan", '\u{0800}'..='\u{083F}'),
+    ("Mandaic", '\u{0840}'..='\u{085F}'),
+    ("Syriac Supplement", '\u{0860}'..='\u{086F}'),
+    ("Arabic Extended-B", '\u{0870}'..='\u{089F}'),
+    ("Arabic Extended-A", '\u{08A0}'..='\u{08FF}'),
+    ("Devanagari", '\u{0900}'..='\u{097F}'),
+    ("Bengali", '\u{0980}'..='\u{09FF}'),
+    ("Gurmukhi", '\u{0A00}'..='\u{0A7F}'),
+    ("Gujarati", '\u{0A80}'..='\u{0AFF}'),
+    ("Oriya", '\u{0B00}'..='\u{0B7F}'),
+    ("Tamil", '\u{0B80}'..='\u{0BFF}'),
+    ("Telugu", '\u{0C00}'..='\u{0C7F}'),
+    ("Kannada", '\u{0C80}'..='\u{0CFF}'),
+    ("Malayalam", '\u{0D00}'..='\u{0D7F}'),
+    ("Sinhala", '\u{0D80}'..='\u{0DFF}'),
+    ("Thai", '\u{0E00}'..='\u{0E7F}'),
+    ("Lao", '\u{0E80}'..='\u{0EFF}'),
+    ("Tibetan", '\u{0F00}'..='\u{0FFF}'),
+    ("Myanmar", '\u{1000}'..='\u{109F}'),
+    ("Georgian", '\u{10A0}'..='\u{10FF}'),
+    ("Hangul Jamo", '\u{1100}'..='\u{11FF}'),
+    ("Ethiopic", '\u{1200}'..='\u{137F}'),
+    ("Ethiopic Supplement", '\u{1380}'..='\u{139F}'),
+    ("Cherokee", '\u{13A0}'..='\u{13FF}'),
+    (
+        "Unified Canadian Aboriginal Syllabics",
+        '\u{1400}'..='\u{167F}',
+    ),
+    ("Ogham", '\u{1680}'..='\u{169F}'),
+    ("Runic", '\u{16A0}'..='\u{16FF}'),
+    ("Tagalog", '\u{1700}'..='\u{171F}'),
+    ("Hanunoo", '\u{1720}'..='\u{173F}'),
+    ("Buhid", '\u{1740}'..='\u{175F}'),
+    ("Tagbanwa", '\u{1760}'..='\u{177F}'),
+    ("Khmer", '\u{1780}'..='\u{17FF}'),
+    ("Mongolian", '\u{1800}'..='\u{18AF}'),
+    (
+        "Unified Canadian Aboriginal Syllabics Extended",
+        '\u{18B0}'..='\u{18FF}',
+    ),
+    ("Limbu", '\u{1900}'..='\u{194F}'),
+    ("Tai Le", '\u{1950}'..='\u{197F}'),
+    ("New Tai Lue", '\u{1980}'..='\u{19DF}'),
+    ("Khmer Symbols", '\u{19E0}'..='\u{19FF}'),
+    ("Buginese", '\u{1A00}'..='\u{1A1F}'),
+    ("Tai Tham", '\u{1A20}'..='\u{1AAF}'),
+    (
+        "Combining Diacritical Marks Extended",
+        '\u{1AB0}'..='\u{1AFF}',
+    ),
+    ("Balinese", '\u{1B00}'..='\u{1B7F}'),
+    ("Sundanese", '\u{1B80}'..='\u{1BBF}'),
+    ("Batak", '\u{1BC0}'..='\u{1BFF}'),
+    ("Lepcha", '\u{1C00}'..='\u{1C4F}'),
+    ("Ol Chiki", '\u{1C50}'..='\u{1C7F}'),
+    ("Cyrillic Extended-C", '\u{1C80}'..='\u{1C8F}'),
+    ("Georgian Extended", '\u{1C90}'..='\u{1CBF}'),
+    ("Sundanese Supplement", '\u{1CC0}'..='\u{1CCF}'),
+    ("Vedic Extensions", '\u{1CD0}'..='\u{1CFF}'),
+    ("Phonetic Extensions", '\u{1D00}'..='\u{1D7F}'),
+    ("Phonetic Extensions Supplement", '\u{1D80}'..='\u{1DBF}'),
+    (
+        "Combining Diacritical Marks Supplement",
+        '\u{1DC0}'..='\u{1DFF}',
+    ),
+    ("Latin Extended Additional", '\u{1E00}'..='\u{1EFF}'),
+    ("Greek Extended", '\u{1F00}'..='\u{1FFF}'),
+    ("General Punctuation", '\u{2000}'..='\u{206F}'),
+    ("Superscripts and Subscripts", '\u{2070}'..='\u{209F}'),
+    ("Currency Symbols", '\u{20A0}'..='\u{20CF}'),
+    (
+        "Combining Diacritical Marks for Symbols",
+        '\u{20D0}'..='\u{20FF}',
+    ),
+    ("Letterlike Symbols", '\u{2100}'..='\u{214F}'),
+    ("Number Forms", '\u{2150}'..='\u{218F}'),
+    ("Arrows", '\u{2190}'..='\u{21FF}'),
+    ("Mathematical Operators", '\u{2200}'..='\u{22FF}'),
+    ("Miscellaneous Technical", '\u{2300}'..='\u{23FF}'),
+    ("Control Pictures", '\u{2400}'..='\u{243F}'),
+    ("Optical Character Recognition", '\u{2440}'..='\u{245F}'),
+    ("Enclosed Alphanumerics", '\u{2460}'..='\u{24FF}'),
+    ("Box Drawing", '\u{2500}'..='\u{257F}'),
+    ("Block Elements", '\u{2580}'..='\u{259F}'),
+    ("Geometric Shapes", '\u{25A0}'..='\u{25FF}'),
+    ("Miscellaneous Symbols", '\u{2600}'..='\u{26FF}'),
+    ("Dingbats", '\u{2700}'..='\u{27BF}'),
+    (
+        "Miscellaneous Mathematical Symbols-A",
+        '\u{27C0}'..='\u{27EF}',
+    ),
+    ("Supplemental Arrows-A", '\u{27F0}'..='\u{27FF}'),
+    ("Braille Patterns", '\u{2800}'..='\u{28FF}'),
+    ("Supplemental Arrows-B", '\u{2900}'..='\u{297F}'),
+    (
+        "Miscellaneous Mathematical Symbols-B",
+        '\u{2980}'..='\u{29FF}',
+    ),
+    (
+        "Supplemental Mathematical Operators",
+        '\u{2A00}'..='\u{2AFF}',
+    ),
+    ("Miscellaneous Symbols and Arrows", '\u{2B00}'..='\u{2BFF}'),
+    ("Glagolitic", '\u{2C00}'..='\u{2C5F}'),
+    ("Latin Extended-C", '\u{2C60}'..='\u{2C7F}'),
+    ("Coptic", '\u{2C80}'..='\u{2CFF}'),
+    ("Georgian Supplement", '\u{2D00}'..='\u{2D2F}'),
+    ("Tifinagh", '\u{2D30}'..='\u{2D7F}'),
+    ("Ethiopic Extended", '\u{2D80}'..='\u{2DDF}'),
+    ("Cyrillic Extended-A", '\u{2DE0}'..='\u{2DFF}'),
+    ("Supplemental Punctuation", '\u{2E00}'..='\u{2E7F}'),
+    ("CJK Radicals Supplement", '\u{2E80}'..='\u{2EFF}'),
+    ("Kangxi Radicals", '\u{2F00}'..='\u{2FDF}'),
+    (
+        "Ideographic Description Characters",
+        '\u{2FF0}'..='\u{2FFF}',
+    ),
+    ("CJK Symbols and Punctuation", '\u{3000}'..='\u{303F}'),
+    ("Hiragana", '\u{3040}'..='\u{309F}'),
+    ("Katakana", '\u{30A0}'..='\u{30FF}'),
+    ("Bopomofo", '\u{3100}'..='\u{312F}'),
+    ("Hangul Compatibility Jamo", '\u{3130}'..='\u{318F}'),
+    ("Kanbun", '\u{3190}'..='\u{319F}'),
+    ("Bopomofo Extended", '\u{31A0}'..='\u{31BF}'),
+    ("CJK Strokes", '\u{31C0}'..='\u{31EF}'),
+    ("Katakana Phonetic Extensions", '\u{31F0}'..='\u{31FF}'),
+    ("Enclosed CJK Letters and Months", '\u{3200}'..='\u{32FF}'),
+    ("CJK Compatibility", '\u{3300}'..='\u{33FF}'),
+    (
+        "CJK Unified Ideographs Extension A",
+        '\u{3400}'..='\u{4DBF}',
+    ),
+    ("Yijing Hexagram Symbols", '\u{4DC0}'..='\u{4DFF}'),
+    ("CJK Unified Ideographs", '\u{4E00}'..='\u{9FFF}'),
+    ("Yi Syllables", '\u{A000}'..='\u{A48F}'),
+    ("Yi Radicals", '\u{A490}'..='\u{A4CF}'),
+    ("Lisu", '\u{A4D0}'..='\u{A4FF}'),
+    ("Vai", '\u{A500}'..='\u{A63F}'),
+    ("Cyrillic Extended-B", '\u{A640}'..='\u{A69F}'),
+    ("Bamum", '\u{A6A0}'..='\u{A6FF}'),
+    ("Modifier Tone Letters", '\u{A700}'..='\u{A71F}'),
+    ("Latin Extended-D", '\u{A720}'..='\u{A7FF}'),
+    ("Syloti Nagri", '\u{A800}'..='\u{A82F}'),
+    ("Common Indic Number Forms", '\u{A830}'..='\u{A83F}'),
+    ("Phags-pa", '\u{A840}'..='\u{A87F}'),
+    ("Saurashtra", '\u{A880}'..='\u{A8DF}'),
+    ("Devanagari Extended", '\u{A8E0}'..='\u{A8FF}'),
+    ("Kayah Li", '\u{A900}'..='\u{A92F}'),
+    ("Rejang", '\u{A930}'..='\u{A95F}'),
+    ("Hangul Jamo Extended-A", '\u{A960}'..='\u{A97F}'),
+    ("Javanese", '\u{A980}'..='\u{A9DF}'),
+    ("Myanmar Extended-B", '\u{A9E0}'..='\u{A9FF}'),
+    ("Cham", '\u{AA00}'..='\u{AA5F}'),
+    ("Myanmar Extended-A", '\u{AA60}'..='\u{AA7F}'),
+    ("Tai Viet", '\u{AA80}'..='\u{AADF}'),
+    ("Meetei Mayek Extensions", '\u{AAE0}'..='\u{AAFF}'),
+    ("Ethiopic Extended-A", '\u{AB00}'..='\u{AB2F}'),
+    ("Latin Extended-E", '\u{AB30}'..='\u{AB6F}'),
+    ("Cherokee Supplement", '\u{AB70}'..='\u{ABBF}'),
+    ("Meetei Mayek", '\u{ABC0}'..='\u{ABFF}'),
+    ("Hangul Syllables", '\u{AC00}'..='\u{D7AF}'),
+    ("Hangul Jamo Extended-B", '\u{D7B0}'..='\u{D7FF}'),
+    ("Private Use Area", '\u{E000}'..='\u{F8FF}'),
+    ("CJK Compatibility Ideographs", '\u{F900}'..='\u{FAFF}'),
+    ("Alphabetic Presentation Forms", '\u{FB00}'..='\u{FB4F}'),
+    ("Arabic Presentation Forms-A", '\u{FB50}'..='\u{FDFF}'),
+    ("Variation Selectors", '\u{FE00}'..='\u{FE0F}'),
+    ("Vertical Forms", '\u{FE10}'..='\u{FE1F}'),
+    ("Combining Half Marks", '\u{FE20}'..='\u{FE2F}'),
+    ("CJK Compatibility Forms", '\u{FE30}'..='\u{FE4F}'),
+    ("Small Form Variants", '\u{FE50}'..='\u{FE6F}'),
+    ("Arabic Presentation Forms-B", '\u{FE70}'..='\u{FEFF}'),
+    ("Halfwidth and Fullwidth Forms", '\u{FF00}'..='\u{FFEF}'),
+    ("Specials", '\u{FFF0}'..='\u{FFFF}'),
+    ("Linear B Syllabary", '\u{10000}'..='\u{1007F}'),
+    ("Linear B Ideograms", '\u{10080}'..='\u{100FF}'),
+    ("Aegean Numbers", '\u{10100}'..='\u{1013F}'),
+    ("Ancient Greek Numbers", '\u{10140}'..='\u{1018F}'),
+    ("Ancient Symbols", '\u{10190}'..='\u{101CF}'),
+    ("Phaistos Disc", '\u{101D0}'..='\u{101FF}'),
+    ("Lycian", '\u{10280}'..='\u{1029F}'),
+    ("Carian", '\u{102A0}'..='\u{102DF}'),
+    ("Coptic Epact Numbers", '\u{102E0}'..='\u{102FF}'),
+    ("Old Italic", '\u{10300}'..='\u{1032F}'),
+    ("Gothic", '\u{10330}'..='\u{1034F}'),
+    ("Old Permic", '\u{10350}'..='\u{1037F}'),
+    ("Ugaritic", '\u{10380}'..='\u{1039F}'),
+    ("Old Persian", '\u{103A0}'..='\u{103DF}'),
+    ("Deseret", '\u{10400}'..='\u{1044F}'),
+    ("Shavian", '\u{10450}'..='\u{1047F}'),
+    ("Osmanya", '\u{10480}'..='\u{104AF}'),
+    ("Osage", '\u{104B0}'..='\u{104FF}'),
+    ("Elbasan", '\u{10500}'..='\u{1052F}'),
+    ("Caucasian Albanian", '\u{10530}'..='\u{1056F}'),
+    ("Vithkuqi", '\u{10570}'..='\u{105BF}'),
+    ("Linear A", '\u{10600}'..='\u{1077F}'),
+    ("Latin Extended-F", '\u{10780}'..='\u{107BF}'),
+    ("Cypriot Syllabary", '\u{10800}'..='\u{1083F}'),
+    ("Imperial Aramaic", '\u{10840}'..='\u{1085F}'),
+    ("Palmyrene", '\u{10860}'..='\u{1087F}'),
+    ("Nabataean", '\u{10880}'..='\u{108AF}'),
+    ("Hatran", '\u{108E0}'..='\u{108FF}'),
+    ("Phoenician", '\u{10900}'..='\u{1091F}'),
+    ("Lydian", '\u{10920}'..='\u{1093F}'),
+    ("Meroitic Hieroglyphs", '\u{10980}'..='\u{1099F}'),
+    ("Meroitic Cursive", '\u{109A0}'..='\u{109FF}'),
+    ("Kharoshthi", '\u{10A00}'..='\u{10A5F}'),
+    ("Old South Arabian", '\u{10A60}'..='\u{10A7F}'),
+    ("Old North Arabian", '\u{10A80}'..='\u{10A9F}'),
+    ("Manichaean", '\u{10AC0}'..='\u{10AFF}'),
+    ("Avestan", '\u{10B00}'..='\u{10B3F}'),
+    ("Inscriptional Parthian", '\u{10B40}'..='\u{10B5F}'),
+    ("Inscriptional Pahlavi", '\u{10B60}'..='\u{10B7F}'),
+    ("Psalter Pahlavi", '\u{10B80}'..='\u{10BAF}'),
+    ("Old Turkic", '\u{10C00}'..='\u{10C4F}'),
+    ("Old Hungarian", '\u{10C80}'..='\u{10CFF}'),
+    ("Hanifi Rohingya", '\u{10D00}'..='\u{10D3F}'),
+    ("Rumi Numeral Symbols", '\u{10E60}'..='\u{10E7F}'),
+    ("Yezidi", '\u{10E80}'..='\u{10EBF}'),
+    ("Arabic Extended-C", '\u{10EC0}'..='\u{10EFF}'),
+    ("Old Sogdian", '\u{10F00}'..='\u{10F2F}'),
+    ("Sogdian", '\u{10F30}'..='\u{10F6F}'),
+    ("Old Uyghur", '\u{10F70}'..='\u{10FAF}'),
+    ("Chorasmian", '\u{10FB0}'..='\u{10FDF}'),
+    ("Elymaic", '\u{10FE0}'..='\u{10FFF}'),
+    ("Brahmi", '\u{11000}'..='\u{1107F}'),
+    ("Kaithi", '\u{11080}'..='\u{110CF}'),
+    ("Sora Sompeng", '\u{110D0}'..='\u{110FF}'),
+    ("Chakma", '\u{11100}'..='\u{1114F}'),
+    ("Mahajani", '\u{11150}'..='\u{1117F}'),
+    ("Sharada", '\u{11180}'..='\u{111DF}'),
+    ("Sinhala Archaic Numbers", '\u{111E0}'..='\u{111FF}'),
+    ("Khojki", '\u{11200}'..='\u{1124F}'),
+    ("Multani", '\u{11280}'..='\u{112AF}'),
+    ("Khudawadi", '\u{112B0}'..='\u{112FF}'),
+    ("Grantha", '\u{11300}'..='\u{1137F}'),
+    ("Newa", '\u{11400}'..='\u{1147F}'),
+    ("Tirhuta", '\u{11480}'..='\u{114DF}'),
+    ("Siddham", '\u{11580}'..='\u{115FF}'),
+    ("Modi", '\u{11600}'..='\u{1165F}'),
+    ("Mongolian Supplement", '\u{11660}'..='\u{1167F}'),
+    ("Takri", '\u{11680}'..='\u{116CF}'),
+    ("Ahom", '\u{11700}'..='\u{1174F}'),
+    ("Dogra", '\u{11800}'..='\u{1184F}'),
+    ("Warang Citi", '\u{118A0}'..='\u{118FF}'),
+    ("Dives Akuru", '\u{11900}'..='\u{1195F}'),
+    ("Nandinagari", '\u{119A0}'..='\u{119FF}'),
+    ("Zanabazar Square", '\u{11A00}'..='\u{11A4F}'),
+    ("Soyombo", '\u{11A50}'..='\u{11AAF}'),
+    (
+        "Unified Canadian Aboriginal Syllabics Extended-A",
+        '\u{11AB0}'..='\u{11ABF}',
+    ),
+    ("Pau Cin Hau", '\u{11AC0}'..='\u{11AFF}'),
+    ("Devanagari Extended-A", '\u{11B00}'..='\u{11B5F}'),
+    ("Bhaiksuki", '\u{11C00}'..='\u{11C6F}'),
+    ("Marchen", '\u{11C70}'..='\u{11CBF}'),
+    ("Masaram Gondi", '\u{11D00}'..='\u{11D5F}'),
+    ("Gunjala Gondi", '\u{11D60}'..='\u{11DAF}'),
+    ("Makasar", '\u{11EE0}'..='\u{11EFF}'),
+    ("Kawi", '\u{11F00}'..='\u{11F5F}'),
+    ("Lisu Supplement", '\u{11FB0}'..='\u{11FBF}'),
+    ("Tamil Supplement", '\u{11FC0}'..='\u{11FFF}'),
+    ("Cuneiform", '\u{12000}'..='\u{123FF}'),
+    (
+        "Cuneiform Numbers and Punctuation",
+        '\u{12400}'..='\u{1247F}',
+    ),
+    ("Early Dynastic Cuneiform", '\u{12480}'..='\u{1254F}'),
+    ("Cypro-Minoan", '\u{12F90}'..='\u{12FFF}'),
+    ("Egyptian Hieroglyphs", '\u{13000}'..='\u{1342F}'),
+    (
+        "Egyptian Hieroglyph Format Controls",
+        '\u{13430}'..='\u{1343F}',
+    ),
+    ("Anatolian Hieroglyphs", '\u{14400}'..='\u{1467F}'),
+    ("Bamum Supplement", '\u{16800}'..='\u{16A3F}'),
+    ("Mro", '\u{16A40}'..='\u{16A6F}'),
+    ("Tangsa", '\u{16A70}'..='\u{16ACF}'),
+    ("Bassa Vah", '\u{16AD0}'..='\u{16AFF}'),
+    ("Pahawh Hmong", '\u{16B00}'..='\u{16B8F}'),
+    ("Medefaidrin", '\u{16E40}'..='\u{16E9F}'),
+    ("Miao", '\u{16F00}'..='\u{16F9F}'),
+    (
+        "Ideographic Symbols and Punctuation",
+        '\u{16FE0}'..='\u{16FFF}',
+    ),
+    ("Tangut", '\u{17000}'..='\u{187FF}'),
+    ("Tangut Components", '\u{18800}'..='\u{18AFF}'),
+    ("Khitan Small Script", '\u{18B00}'..='\u{18CFF}'),
+    ("Tangut Supplement", '\u{18D00}'..='\u{18D7F}'),
+    ("Kana Extended-B", '\u{1AFF0}'..='\u{1AFFF}'),
+    ("Kana Supplement", '\u{1B000}'..='\u{1B0FF}'),
+    ("Kana Extended-A", '\u{1B100}'..='\u{1B12F}'),
+    ("Small Kana Extension", '\u{1B130}'..='\u{1B16F}'),
+    ("Nushu", '\u{1B170}'..='\u{1B2FF}'),
+    ("Duployan", '\u{1BC00}'..='\u{1BC9F}'),
+    ("Shorthand Format Controls", '\u{1BCA0}'..='\u{1BCAF}'),
+    ("Znamenny Musical Notation", '\u{1CF00}'..='\u{1CFCF}'),
+    ("Byzantine Musical Symbols", '\u{1D000}'..='\u{1D0FF}'),
+    ("Musical Symbols", '\u{1D100}'..='\u{1D1FF}'),
+    ("Ancient Greek Musical Notation", '\u{1D200}'..='\u{1D24F}'),
+    ("Kaktovik Numerals", '\u{1D2C0}'..='\u{1D2DF}'),
+    ("Mayan Numerals", '\u{1D2E0}'..='\u{1D2FF}'),
+    ("Tai Xuan Jing Symbols", '\u{1D300}'..='\u{1D35F}'),
+    ("Counting Rod Numerals", '\u{1D360}'..='\u{1D37F}'),
+    (
+        "Mathematical Alphanumeric Symbols",
+        '\u{1D400}'..='\u{1D7FF}',
+    ),
+    ("Sutton SignWriting", '\u{1D800}'..='\u{1DAAF}'),
+    ("Latin Extended-G", '\u{1DF00}'..='\u{1DFFF}'),
+    ("Glagolitic Supplement", '\u{1E000}'..='\u{1E02F}'),
+    ("Cyrillic Extended-D", '\u{1E030}'..='\u{1E08F}'),
+    ("Nyiakeng Puachue Hmong", '\u{1E100}'..='\u{1E14F}'),
+    ("Toto", '\u{1E290}'..='\u{1E2BF}'),
+    ("Wancho", '\u{1E2C0}'..='\u{1E2FF}'),
+    ("Nag Mundari", '\u{1E4D0}'..='\u{1E4FF}'),
+    ("Ethiopic Extended-B", '\u{1E7E0}'..='\u{1E7FF}'),
+    ("Mende Kikakui", '\u{1E800}'..='\u{1E8DF}'),
+    ("Adlam", '\u{1E900}'..='\u{1E95F}'),
+    ("Indic Siyaq Numbers", '\u{1EC70}'..='\u{1ECBF}'),
+    ("Ottoman Siyaq Numbers", '\u{1ED00}'..='\u{1ED4F}'),
+    (
+        "Arabic Mathematical Alphabetic Symbols",
+        '\u{1EE00}'..='\u{1EEFF}',
+    ),
+    ("Mahjong Tiles", '\u{1F000}'..='\u{1F02F}'),
+    ("Domino Tiles", '\u{1F030}'..='\u{1F09F}'),
+    ("Playing Cards", '\u{1F0A0}'..='\u{1F0FF}'),
+    (
+        "Enclosed Alphanumeric Supplement",
+        '\u{1F100}'..='\u{1F1FF}',
+    ),
+    ("Enclosed Ideographic Supplement", '\u{1F200}'..='\u{1F2FF}'),
+    (
+        "Miscellaneous Symbols and Pictographs",
+        '\u{1F300}'..='\u{1F5FF}',
+    ),
+    ("Emoticons", '\u{1F600}'..='\u{1F64F}'),
+    ("Ornamental Dingbats", '\u{1F650}'..='\u{1F67F}'),
+    ("Transport and Map Symbols", '\u{1F680}'..='\u{1F6FF}'),
+    ("Alchemical Symbols", '\u{1F700}'..='\u{1F77F}'),
+    ("Geometric Shapes Extended", '\u{1F780}'..='\u{1F7FF}'),
+    ("Supplemental Arrows-C", '\u{1F800}'..='\u{1F8FF}'),
+    (
+        "Supplemental Symbols and Pictographs",
+        '\u{1F900}'..='\u{1F9FF}',
+    ),
+    ("Chess Symbols", '\u{1FA00}'..='\u{1FA6F}'),
+    (
+        "Symbols and Pictographs Extended-A",
+        '\u{1FA70}'..='\u{1FAFF}',
+    ),
+    ("Symbols for Legacy Computing", '\u{1FB00}'..='\u{1FBFF}'),
+    (
+        "CJK Unified Ideographs Extension B",
+        '\u{20000}'..='\u{2A6DF}',
+    ),
+    (
+        "CJK Unified Ideographs Extension C",
+        '\u{2A700}'..='\u{2B73F}',
+    ),
+    (
+        "CJK Unified Ideographs Extension D",
+        '\u{2B740}'..='\u{2B81F}',
+    ),
+    (
+        "CJK Unified Ideographs Extension E",
+        '\u{2B820}'..='\u{2CEAF}',
+    ),
+    (
+        "CJK Unified Ideographs Extension F",
+        '\u{2CEB0}'..='\u{2EBEF}',
+    ),
+    (
+        "CJK Unified Ideographs Extension I",
+        '\u{2EBF0}'..='\u{2EE5F}',
+    ),
+    (
+        "CJK Compatibility Ideographs Supplement",
+        '\u{2F800}'..='\u{2FA1F}',
+    ),
+    (
+        "CJK Unified Ideographs Extension G",
+        '\u{30000}'..='\u{3134F}',
+    ),
+    (
+        "CJK Unified Ideographs Extension H",
+        '\u{31350}'..='\u{323AF}',
+    ),
+    ("Tags", '\u{E0000}'..='\u{E007F}'),
+    ("Variation Selectors Supplement", '\u{E0100}'..='\u{E01EF}'),
+    (
+        "Supplementary Private Use Area-A",
+        '\u{F0000}'..='\u{FFFFF}',
+    ),
+    (
+        "Supplementary Private Use Area-B",
+        '\u{100000}'..='\u{10FFFF}',
+    ),
+];
+
+// The `custom_chars` entries compiled into sorted, non-overlapping ranges.
+// The entries are kept so the config can be written back out as it was read.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct CharSet {
+    entries: Vec<String>,
+    ranges: Vec<RangeInclusive<char>>,
+}
+
+impl CharSet {
+    pub fn contains(&self, ch: char) -> bool {
+        self.ranges
+            .binary_search_by(|range| {
+                if *range.end() < ch {
+                    Ordering::Less
+                } else if *range.start() > ch {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+impl TryFrom<Vec<String>> for CharSet {
+    type Error = String;
+
+    fn try_from(entries: Vec<String>) -> Result<Self, Self::Error> {
+        let mut ranges = Vec::new();
+        for entry in &entries {
+            let parsed = parse_entry(entry).ok_or_else(|| {
+                format!(
+                    "invalid character {entry:?}, expected a codepoint such as \"U+200B\", \
+                     a range such as \"U+E0000..U+E007F\", a literal character, a general \
+                     category such as \"Cf\", a script or a block name"
+                )
+            })?;
+            ranges.extend(parsed);
+        }
+
+        ranges.sort_by_key(|range| *range.start());
+        let mut merged: Vec<RangeInclusive<char>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if (*range.start() as u32) <= (*last.end() as u32).saturating_add(1) => {
+                    *last = *last.start()..=(*last.end()).max(*range.end());
+                }
+                _ => merged.push(range),
+            }
+        }
+
+        Ok(Self {
+            entries,
+            ranges: merged,
+        })
+    }
+}
+
+impl From<CharSet> for Vec<String> {
+    fn from(set: CharSet) -> Self {
+        set.entries
+    }
+}
+
+fn parse_entry(entry: &str) -> Option<Vec<RangeInclusive<char>>> {
+    // A lone literal is taken as is, so whitespace characters can be listed
+    let mut chars = entry.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(vec![ch..=ch]);
+    }
+    // Only ASCII spaces are padding; other whitespace is never trimmed away
+    let entry = entry.trim_matches(' ');
+
+    if let Some((start, end)) = entry.split_once("..") {
+        let start = parse_char(start.trim_matches(' '))?;
+        let end = parse_char(end.trim_start_matches('=').trim_matches(' '))?;
+        return (start <= end).then(|| vec![start..=end]);
+    }
+    if let Some(ch) = parse_char(entry) {
+        return Some(vec![ch..=ch]);
+    }
+    if let Some(ranges) = unicode_property(entry) {
+        return Some(ranges);
+    }
+    if let Some(block) = find_block(entry) {
+        return Some(vec![block]);
+    }
+    // Bare hex without the "U+" prefix, as earlier versions accepted
+    parse_hex(entry).map(|ch| vec![ch..=ch])
+}
+
+// A `U+XXXX` codepoint or a single literal character
+fn parse_char(spec: &str) -> Option<char> {
+    if let Some(hex) = spec.strip_prefix("U+").or_else(|| spec.strip_prefix("u+")) {
+        return parse_hex(hex);
+    }
+    let mut chars = spec.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => None,
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<char> {
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+// Block names match loosely: case, spaces, hyphens and underscores are ignored
+fn find_block(name: &str) -> Option<RangeInclusive<char>> {
+    let name = loose_name(name);
+    NAMED_BLOCKS
+        .iter()
+        .find(|(block, _)| loose_name(block) == name)
+        .map(|(_, range)| range.clone())
+}
+
+fn loose_name(name: &str) -> String {
+    name.chars()
+        .filter(|ch| !matches!(ch, ' ' | '-' | '_'))
+        .map(|ch| ch.to_ascii_lowercase())
+        .collect()
+}
+
+// General categories, scripts and binary properties, resolved the way a
+// regex `\p{...}` class would be
+fn unicode_property(name: &str) -> Option<Vec<RangeInclusive<char>>> {
+    let valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, ' ' | '-' | '_' | '='));
+    if !valid_name {
+        return None;
+    }
+
+    let hir = ParserBuilder::new()
+        .build()
+        .parse(&format!("\\p{{{name}}}"))
+        .ok()?;
+    match hir.kind() {
+        HirKind::Class(Class::Unicode(class)) => Some(
+            class
+                .ranges()
+                .iter()
+                .map(|range| range.start()..=range.end())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(entries: &[&str]) -> Result<CharSet, String> {
+        CharSet::try_from(
+            entries
+                .iter()
+                .map(|entry| entry.to_string())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn parses_codepoints_and_ranges() {
+        let chars = set(&[
+            "U+200B",
+            "u+2060",
+            "U+E0000..U+E007F",
+            " U+2000 ..= U+2002 ",
+        ])
+        .unwrap();
+        for ch in ['\u{200B}', '\u{2060}', '\u{E0000}', '\u{E007F}', '\u{2001}'] {
+            assert!(chars.contains(ch), "{ch:?}");
+        }
+        assert!(!chars.contains('\u{200C}'));
+        assert!(!chars.contains('\u{E0080}'));
+    }
+
+    #[test]
+    fn parses_literal_characters_including_whitespace() {
+        let chars = set(&["·", "\u{3000}", "\u{00A0}", " "]).unwrap();
+        for ch in ['·', '\u{3000}', '\u{00A0}', ' '] {
+            assert!(chars.contains(ch), "{ch:?}");
+        }
+        assert!(!chars.contains('\t'));
+    }
+
+    #[test]
+    fn parses_blocks_categories_and_scripts() {
+        let chars = set(&["private use area", "Cf", "Cyrillic"]).unwrap();
+        for ch in ['\u{E000}', '\u{200D}', 'Ж'] {
+            assert!(chars.contains(ch), "{ch:?}");
+        }
+        assert!(!chars.contains('a'));
+    }
+
+    #[test]
+    fn knows_every_block() {
+        let forms = set(&[
+            "Arabic Presentation Forms-A",
+            "cjk unified ideographs extension b",
+        ])
+        .unwrap();
+        assert!(forms.contains('\u{FB50}'));
+        assert!(forms.contains('\u{20000}'));
+        assert!(!forms.contains('\u{0627}'));
+
+        // The Arabic script reaches beyond the Arabic block
+        let arabic = set(&["Arabic"]).unwrap();
+        assert!(arabic.contains('\u{0627}'));
+        assert!(arabic.contains('\u{FB50}'));
+    }
+
+    #[test]
+    fn merges_overlapping_ranges() {
+        let chars = set(&["U+10..U+20", "U+15..U+30", "U+31"]).unwrap();
+        assert_eq!(chars.ranges, ['\u{10}'..='\u{31}']);
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        for entry in ["U+ZZZZ", "U+2060..U+2000", "no such block", "xyz"] {
+            assert!(set(&[entry]).is_err(), "{entry:?}");
+        }
+    }
+}
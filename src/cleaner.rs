@@ -0,0 +1,1151 @@
+use crate::charset::CharSet;
+use crate::config::{BidiControlMode, BomPolicy, LineEndings, TargetCharacters};
+use crate::directives::Suppressions;
+use serde::{Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+pub fn format_codepoint(ch: char) -> String {
+    format!("U+{:04X}", ch as u32)
+}
+
+pub fn serialize_codepoint<S: Serializer>(ch: &char, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_codepoint(*ch))
+}
+
+fn serialize_replacement<S: Serializer>(
+    ch: &Option<char>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match ch {
+        Some(ch) => serialize_codepoint(ch, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    ZeroWidthSpace,
+    NonBreakingSpace,
+    ControlCharacter,
+    UnicodeWhitespace,
+    TrailingWhitespace,
+    WhitespaceOnlyLine,
+    TagCharacter,
+    BidiControl,
+    Custom,
+    LineEnding,
+    ByteOrderMark,
+}
+
+impl Category {
+    pub const ALL: [Category; 11] = [
+        Category::ZeroWidthSpace,
+        Category::NonBreakingSpace,
+        Category::ControlCharacter,
+        Category::UnicodeWhitespace,
+        Category::TrailingWhitespace,
+        Category::WhitespaceOnlyLine,
+        Category::TagCharacter,
+        Category::BidiControl,
+        Category::Custom,
+        Category::LineEnding,
+        Category::ByteOrderMark,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Category::ZeroWidthSpace => "zero-width-space",
+            Category::NonBreakingSpace => "non-breaking-space",
+            Category::ControlCharacter => "control-character",
+            Category::UnicodeWhitespace => "unicode-whitespace",
+            Category::TrailingWhitespace => "trailing-whitespace",
+            Category::WhitespaceOnlyLine => "whitespace-only-line",
+            Category::TagCharacter => "tag-character",
+            Category::BidiControl => "bidi-control",
+            Category::Custom => "custom-character",
+            Category::LineEnding => "line-ending",
+            Category::ByteOrderMark => "byte-order-mark",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Category::ZeroWidthSpace => "Zero width space, joiner or stray U+FEFF",
+            Category::NonBreakingSpace => "Non-breaking space",
+            Category::ControlCharacter => "ASCII control character",
+            Category::UnicodeWhitespace => "Non-ASCII Unicode whitespace",
+            Category::TrailingWhitespace => "Whitespace at the end of a line",
+            Category::WhitespaceOnlyLine => "Line containing only whitespace",
+            Category::TagCharacter => "Unicode tag character that can hide ASCII text",
+            Category::BidiControl => "Bidirectional control character (Trojan Source)",
+            Category::Custom => "Character listed in custom_chars",
+            Category::LineEnding => "Line ending converted by the line_endings setting",
+            Category::ByteOrderMark => "Byte order mark changed by the bom setting",
+        }
+    }
+}
+
+// Serialized by id so JSON, SARIF and the text summary spell categories alike
+impl Serialize for Category {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Removed,
+    Replaced,
+    Inserted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub line: usize,
+    pub column: usize,
+    #[serde(serialize_with = "serialize_codepoint")]
+    pub codepoint: char,
+    pub category: Category,
+    pub action: Action,
+    #[serde(
+        serialize_with = "serialize_replacement",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub replacement: Option<char>,
+}
+
+impl Finding {
+    fn new(
+        line: usize,
+        column: usize,
+        codepoint: char,
+        category: Category,
+        action: Action,
+    ) -> Self {
+        Self {
+            line,
+            column,
+            codepoint,
+            category,
+            action,
+            replacement: None,
+        }
+    }
+}
+
+// The cleaned text along with one finding per character that was removed,
+// replaced or inserted to produce it.
+pub struct CleanOutput<'a> {
+    pub content: Cow<'a, str>,
+    pub edits: Vec<Finding>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharAction {
+    Keep,
+    Remove(Category),
+    Replace(char, Category),
+    // Tag characters, joiners and unbalanced bidi controls depend on the
+    // characters around them
+    Contextual,
+}
+
+// The enabled targets compiled into lookup tables, so each character is
+// classified once instead of once per target.
+pub struct Cleaner {
+    ascii: [CharAction; 128],
+    non_ascii: Vec<(char, CharAction)>,
+    // Non-ASCII custom characters, which can span whole blocks and are
+    // checked only when no other target has claimed a character
+    custom: CharSet,
+    // Characters exempt from every target, trailing whitespace included
+    allow: CharSet,
+    unbalanced_bidi: bool,
+    trailing_whitespace: bool,
+    whitespace_only_lines: bool,
+    // Markdown prose keeps hard line breaks and intentional non-breaking spaces
+    markdown_prose: bool,
+    line_endings: LineEndings,
+    bom: BomPolicy,
+    // Removes bidi controls and tag characters from lines that inline
+    // directives suppress, unless directives may exempt those too
+    suppressed_lines: Option<Box<Cleaner>>,
+}
+
+impl Cleaner {
+    pub fn new(targets: &TargetCharacters, line_endings: LineEndings, bom: BomPolicy) -> Self {
+        Self::build(targets, line_endings, bom, false)
+    }
+
+    pub fn for_markdown_prose(
+        targets: &TargetCharacters,
+        line_endings: LineEndings,
+        bom: BomPolicy,
+    ) -> Self {
+        Self::build(targets, line_endings, bom, true)
+    }
+
+    fn build(
+        targets: &TargetCharacters,
+        line_endings: LineEndings,
+        bom: BomPolicy,
+        markdown_prose: bool,
+    ) -> Self {
+        let mut table = BTreeMap::new();
+
+        // Insertion order mirrors precedence: the first target to claim a
+        // character decides what happens to it.
+        if targets.zero_width_spaces {
+            claim(
+                &mut table,
+                ['\u{200B}', '\u{FEFF}'],
+                CharAction::Remove(Category::ZeroWidthSpace),
+            );
+            // Joiners are only removed outside the text that needs them
+            claim(&mut table, ['\u{200C}', '\u{200D}'], CharAction::Contextual);
+        }
+
+        if markdown_prose {
+            // Intentional in prose, so no later target may claim it either
+            claim(&mut table, ['\u{00A0}'], CharAction::Keep);
+        } else if targets.non_breaking_spaces {
+            let action = CharAction::Replace(' ', Category::NonBreakingSpace);
+            claim(&mut table, ['\u{00A0}'], action);
+        }
+
+        if targets.control_characters {
+            let chars = ('\0'..='\u{1F}')
+                .filter(|ch| !matches!(ch, '\n' | '\r' | '\t'))
+                .chain(['\u{7F}']);
+            claim(
+                &mut table,
+                chars,
+                CharAction::Remove(Category::ControlCharacter),
+            );
+        }
+
+        if targets.unicode_whitespace {
+            // NBSP is left to its own target, so disabling that one keeps it
+            let chars = ('\0'..='\u{3000}').filter(|ch| {
+                ch.is_whitespace() && !matches!(ch, ' ' | '\n' | '\r' | '\t' | '\u{00A0}')
+            });
+            claim(
+                &mut table,
+                chars,
+                CharAction::Remove(Category::UnicodeWhitespace),
+            );
+        }
+
+        if targets.tag_characters {
+            claim(
+                &mut table,
+                '\u{E0000}'..='\u{E007F}',
+                CharAction::Contextual,
+            );
+        }
+
+        match targets.bidi_controls {
+            BidiControlMode::Strip => {
+                // The marks are routine in right-to-left text, so they can be
+                // left out of stripping on their own
+                let chars = BIDI_CONTROLS
+                    .iter()
+                    .map(|&(ch, _)| ch)
+                    .filter(|&ch| targets.directional_marks || !is_directional_mark(ch));
+                claim(&mut table, chars, CharAction::Remove(Category::BidiControl));
+            }
+            BidiControlMode::Unbalanced => {
+                // Marks have nothing to terminate, so only embeddings and isolates qualify
+                let chars = BIDI_CONTROLS
+                    .iter()
+                    .map(|&(ch, _)| ch)
+                    .filter(|&ch| !is_directional_mark(ch));
+                claim(&mut table, chars, CharAction::Contextual);
+            }
+            BidiControlMode::Off => {}
+        }
+
+        // Custom characters come last; only their ASCII part goes in the table
+        let custom = targets.custom_chars.clone();
+        let custom_ascii = ('\0'..='\u{7F}').filter(|&ch| custom.contains(ch));
+        claim(
+            &mut table,
+            custom_ascii,
+            CharAction::Remove(Category::Custom),
+        );
+
+        // The allowlist takes precedence over every target
+        let allow = targets.allow_chars.clone();
+        table.retain(|&ch, _| !allow.contains(ch));
+
+        let mut ascii = [CharAction::Keep; 128];
+        let mut non_ascii = Vec::new();
+        for (ch, action) in table {
+            if ch.is_ascii() {
+                ascii[ch as usize] = action;
+            } else {
+                non_ascii.push((ch, action));
+            }
+        }
+
+        let suppressed_lines = (!targets.directives_suppress_bidi_and_tags
+            && (targets.tag_characters || targets.bidi_controls != BidiControlMode::Off))
+            .then(|| {
+                let attack_targets = TargetCharacters {
+                    zero_width_spaces: false,
+                    non_breaking_spaces: false,
+                    control_characters: false,
+                    unicode_whitespace: false,
+                    trailing_whitespace: false,
+                    whitespace_only_lines: false,
+                    tag_characters: targets.tag_characters,
+                    bidi_controls: targets.bidi_controls,
+                    directional_marks: targets.directional_marks,
+                    custom_chars: CharSet::default(),
+                    allow_chars: allow.clone(),
+                    directives_suppress_bidi_and_tags: true,
+                };
+                Box::new(Self::build(
+                    &attack_targets,
+                    LineEndings::Preserve,
+                    BomPolicy::Keep,
+                    false,
+                ))
+            });
+
+        Self {
+            ascii,
+            non_ascii,
+            custom,
+            allow,
+            unbalanced_bidi: targets.bidi_controls == BidiControlMode::Unbalanced,
+            trailing_whitespace: targets.trailing_whitespace,
+            whitespace_only_lines: targets.whitespace_only_lines,
+            markdown_prose,
+            line_endings,
+            bom,
+            suppressed_lines,
+        }
+    }
+
+    pub fn clean<'a>(&self, content: &'a str, suppressed: &Suppressions) -> CleanOutput<'a> {
+        self.clean_from(content, 1, suppressed)
+    }
+
+    // Cleans `content` as a fragment of a larger file whose first line is
+    // `first_line`, leaving lines suppressed by inline directives untouched
+    pub fn clean_from<'a>(
+        &self,
+        content: &'a str,
+        first_line: usize,
+        suppressed: &Suppressions,
+    ) -> CleanOutput<'a> {
+        // Only a U+FEFF at the very start of the file is a byte order mark
+        let at_file_start = first_line == 1 && !suppressed.contains(1);
+        let (had_bom, text) = match content.strip_prefix('\u{FEFF}') {
+            Some(rest) if at_file_start => (true, rest),
+            _ => (false, content),
+        };
+        let wants_bom = at_file_start
+            && match self.bom {
+                BomPolicy::Strip => false,
+                BomPolicy::Keep => had_bom,
+                BomPolicy::Add => !content.is_empty(),
+            };
+
+        let CleanOutput {
+            content: cleaned,
+            mut edits,
+        } = self.clean_lines(text, first_line, suppressed);
+        if had_bom {
+            // Columns on the first line still count the byte order mark
+            for edit in edits.iter_mut().filter(|edit| edit.line == 1) {
+                edit.column += 1;
+            }
+        }
+        if had_bom != wants_bom {
+            let action = if had_bom {
+                Action::Removed
+            } else {
+                Action::Inserted
+            };
+            let edit = Finding::new(1, 1, '\u{FEFF}', Category::ByteOrderMark, action);
+            edits.insert(0, edit);
+        }
+
+        let content = match cleaned {
+            cleaned if !wants_bom => cleaned,
+            Cow::Borrowed(_) if had_bom => Cow::Borrowed(content),
+            cleaned => Cow::Owned(format!("\u{FEFF}{cleaned}")),
+        };
+        CleanOutput { content, edits }
+    }
+
+    fn clean_lines<'a>(
+        &self,
+        content: &'a str,
+        first_line: usize,
+        suppressed: &Suppressions,
+    ) -> CleanOutput<'a> {
+        if content.is_ascii() && self.is_clean_ascii(content) {
+            return CleanOutput {
+                content: Cow::Borrowed(content),
+                edits: Vec::new(),
+            };
+        }
+
+        // Nothing is allocated until the first line that actually changes
+        let mut output: Option<String> = None;
+        let mut edits = Vec::new();
+        let mut line_buf = String::new();
+        let mut offset = 0;
+
+        for (idx, line) in content.split_inclusive('\n').enumerate() {
+            let line_number = first_line + idx;
+            if suppressed.contains(line_number) {
+                // Everything but bidi controls and tag characters stays as is
+                let (body, ending) = split_line_ending(line);
+                let body_changed = self.suppressed_lines.as_ref().is_some_and(|attacks| {
+                    attacks.clean_line(line_number, body, &mut line_buf, &mut edits)
+                });
+                if output.is_none() && body_changed {
+                    let mut cleaned = String::with_capacity(content.len());
+                    cleaned.push_str(&content[..offset]);
+                    output = Some(cleaned);
+                }
+                if let Some(cleaned) = output.as_mut() {
+                    cleaned.push_str(if body_changed { &line_buf } else { body });
+                    cleaned.push_str(ending);
+                }
+                offset += line.len();
+                continue;
+            }
+
+            let (body, ending) = split_line_ending(line);
+            let new_ending = self.normalize_ending(ending);
+            let body_changed = self.clean_line(line_number, body, &mut line_buf, &mut edits);
+
+            if new_ending != ending {
+                let column = body.chars().count() + 1;
+                let action = if ending.len() > new_ending.len() {
+                    Action::Removed
+                } else {
+                    Action::Inserted
+                };
+                edits.push(Finding::new(
+                    line_number,
+                    column,
+                    '\r',
+                    Category::LineEnding,
+                    action,
+                ));
+            }
+
+            if output.is_none() && (body_changed || new_ending != ending) {
+                let mut cleaned = String::with_capacity(content.len());
+                cleaned.push_str(&content[..offset]);
+                output = Some(cleaned);
+            }
+
+            if let Some(cleaned) = output.as_mut() {
+                cleaned.push_str(if body_changed { &line_buf } else { body });
+                cleaned.push_str(new_ending);
+            }
+            offset += line.len();
+        }
+
+        CleanOutput {
+            content: output.map_or(Cow::Borrowed(content), Cow::Owned),
+            edits,
+        }
+    }
+
+    pub fn directives_suppress_tag_characters(&self) -> bool {
+        self.suppressed_lines.is_none()
+    }
+
+    pub fn strips_tag_characters(&self) -> bool {
+        self.lookup('\u{E0001}') != CharAction::Keep
+    }
+
+    fn lookup(&self, ch: char) -> CharAction {
+        if ch.is_ascii() {
+            return self.ascii[ch as usize];
+        }
+
+        match self
+            .non_ascii
+            .binary_search_by_key(&ch, |&(entry, _)| entry)
+        {
+            Ok(idx) => self.non_ascii[idx].1,
+            Err(_) if self.custom.contains(ch) && !self.allow.contains(ch) => {
+                CharAction::Remove(Category::Custom)
+            }
+            Err(_) => CharAction::Keep,
+        }
+    }
+
+    fn is_clean_ascii(&self, content: &str) -> bool {
+        content
+            .bytes()
+            .all(|byte| self.ascii[byte as usize] == CharAction::Keep)
+            && content.split_inclusive('\n').all(|line| {
+                let (body, ending) = split_line_ending(line);
+                !self.line_needs_rewrite(body) && self.normalize_ending(ending) == ending
+            })
+    }
+
+    // Whitespace-only lines are governed by their own switch, so turning it off
+    // also keeps trailing whitespace trimming away from indented blank lines
+    fn line_needs_rewrite(&self, body: &str) -> bool {
+        let trimmed = self.trim_end(body);
+        if trimmed.is_empty() {
+            return self.whitespace_only_lines && !body.is_empty();
+        }
+        self.trailing_whitespace
+            && trimmed.len() != body.len()
+            && !(self.markdown_prose && is_hard_break(&body[trimmed.len()..]))
+    }
+
+    // Writes the cleaned line into `buf`, records each edit in `edits` and
+    // reports whether the line differs from `body`
+    fn clean_line(
+        &self,
+        line: usize,
+        body: &str,
+        buf: &mut String,
+        edits: &mut Vec<Finding>,
+    ) -> bool {
+        if !self.line_needs_rewrite(body)
+            && body.chars().all(|ch| self.lookup(ch) == CharAction::Keep)
+        {
+            return false;
+        }
+
+        buf.clear();
+        let line_edits = edits.len();
+        let unbalanced = if self.unbalanced_bidi {
+            unbalanced_bidi_offsets(body)
+        } else {
+            Vec::new()
+        };
+        // Original column and character behind each character of `buf`, so
+        // whitespace trimmed afterwards can still be reported where it was found
+        let mut kept: Vec<(usize, char)> = Vec::new();
+        let mut chars = body.char_indices().zip(1..).peekable();
+
+        while let Some(((offset, ch), column)) = chars.next() {
+            match self.lookup(ch) {
+                CharAction::Keep => {
+                    buf.push(ch);
+                    kept.push((column, ch));
+                }
+                CharAction::Remove(category) => {
+                    edits.push(Finding::new(line, column, ch, category, Action::Removed));
+                }
+                CharAction::Replace(replacement, category) => {
+                    buf.push(replacement);
+                    kept.push((column, ch));
+                    edits.push(Finding {
+                        replacement: Some(replacement),
+                        ..Finding::new(line, column, ch, category, Action::Replaced)
+                    });
+                }
+                CharAction::Contextual if is_tag_char(ch) => {
+                    let mut run = vec![(column, ch)];
+                    while let Some(((_, next), column)) =
+                        chars.next_if(|&((_, next), _)| is_tag_char(next))
+                    {
+                        run.push((column, next));
+                    }
+                    let tags = run.iter().map(|&(_, ch)| ch);
+                    if is_emoji_flag_tags(buf.chars().next_back(), tags) {
+                        buf.extend(run.iter().map(|&(_, ch)| ch));
+                        kept.extend(run);
+                    } else {
+                        edits.extend(run.into_iter().map(|(column, ch)| {
+                            Finding::new(line, column, ch, Category::TagCharacter, Action::Removed)
+                        }));
+                    }
+                }
+                CharAction::Contextual if is_joiner(ch) => {
+                    let next = body[offset + ch.len_utf8()..].chars().next();
+                    if is_meaningful_joiner(ch, &body[..offset], next) {
+                        buf.push(ch);
+                        kept.push((column, ch));
+                    } else {
+                        edits.push(Finding::new(
+                            line,
+                            column,
+                            ch,
+                            Category::ZeroWidthSpace,
+                            Action::Removed,
+                        ));
+                    }
+                }
+                CharAction::Contextual => {
+                    if unbalanced.contains(&offset) {
+                        edits.push(Finding::new(
+                            line,
+                            column,
+                            ch,
+                            Category::BidiControl,
+                            Action::Removed,
+                        ));
+                    } else {
+                        buf.push(ch);
+                        kept.push((column, ch));
+                    }
+                }
+            }
+        }
+
+        // Remove lines that contain only whitespace (spaces, tabs)
+        let (trimmed_len, category) = if self.trim_end(buf).is_empty() {
+            let kept_len = if self.whitespace_only_lines {
+                0
+            } else {
+                buf.len()
+            };
+            (kept_len, Category::WhitespaceOnlyLine)
+        } else if self.trailing_whitespace {
+            let trimmed_len = self.trim_end(buf).len();
+            if self.markdown_prose && is_hard_break(&buf[trimmed_len..]) {
+                (buf.len(), Category::TrailingWhitespace)
+            } else {
+                (trimmed_len, Category::TrailingWhitespace)
+            }
+        } else {
+            (buf.len(), Category::TrailingWhitespace)
+        };
+        let trimmed_count = buf[trimmed_len..].chars().count();
+        for &(column, ch) in &kept[kept.len() - trimmed_count..] {
+            // A replaced NBSP that ends up trimmed is reported once, as removed
+            if let Some(idx) = edits[line_edits..]
+                .iter()
+                .position(|edit| edit.column == column)
+            {
+                edits.remove(line_edits + idx);
+            }
+            edits.push(Finding::new(line, column, ch, category, Action::Removed));
+        }
+        buf.truncate(trimmed_len);
+        edits[line_edits..].sort_by_key(|edit| edit.column);
+
+        buf != body
+    }
+
+    // Trims trailing whitespace other than allowlisted characters
+    fn trim_end<'a>(&self, text: &'a str) -> &'a str {
+        text.trim_end_matches(|ch: char| ch.is_whitespace() && !self.allow.contains(ch))
+    }
+
+    fn normalize_ending<'a>(&self, ending: &'a str) -> &'a str {
+        match self.line_endings {
+            _ if ending.is_empty() => ending,
+            LineEndings::Preserve => ending,
+            LineEndings::Lf => "\n",
+            LineEndings::Crlf => "\r\n",
+        }
+    }
+}
+
+fn claim(
+    table: &mut BTreeMap<char, CharAction>,
+    chars: impl IntoIterator<Item = char>,
+    action: CharAction,
+) {
+    for ch in chars {
+        table.entry(ch).or_insert(action);
+    }
+}
+
+// Two or more trailing spaces end a Markdown line with a hard break
+fn is_hard_break(trailing: &str) -> bool {
+    trailing.len() >= 2 && trailing.bytes().all(|byte| byte == b' ')
+}
+
+fn split_line_ending(line: &str) -> (&str, &str) {
+    if let Some(body) = line.strip_suffix("\r\n") {
+        (body, "\r\n")
+    } else if let Some(body) = line.strip_suffix('\n') {
+        (body, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+const BIDI_CONTROLS: [(char, &str); 12] = [
+    ('\u{202A}', "LRE"),
+    ('\u{202B}', "RLE"),
+    ('\u{202C}', "PDF"),
+    ('\u{202D}', "LRO"),
+    ('\u{202E}', "RLO"),
+    ('\u{2066}', "LRI"),
+    ('\u{2067}', "RLI"),
+    ('\u{2068}', "FSI"),
+    ('\u{2069}', "PDI"),
+    ('\u{200E}', "LRM"),
+    ('\u{200F}', "RLM"),
+    ('\u{061C}', "ALM"),
+];
+
+fn is_directional_mark(ch: char) -> bool {
+    matches!(ch, '\u{200E}' | '\u{200F}' | '\u{061C}')
+}
+
+pub fn bidi_control_name(ch: char) -> Option<&'static str> {
+    BIDI_CONTROLS
+        .iter()
+        .find(|&&(control, _)| control == ch)
+        .map(|&(_, name)| name)
+}
+
+fn is_bidi_isolate(ch: char) -> bool {
+    matches!(ch, '\u{2066}' | '\u{2067}' | '\u{2068}')
+}
+
+// Embeddings and isolates are terminated at the end of each paragraph, so
+// anything still open at the end of the line is what reorders the text after it.
+fn unbalanced_bidi_offsets(line: &str) -> Vec<usize> {
+    let mut unbalanced = Vec::new();
+    let mut open: Vec<(usize, char)> = Vec::new();
+
+    for (offset, ch) in line.char_indices() {
+        match ch {
+            '\u{202A}' | '\u{202B}' | '\u{202D}' | '\u{202E}' | '\u{2066}' | '\u{2067}'
+            | '\u{2068}' => open.push((offset, ch)),
+            '\u{202C}' => match open.last() {
+                Some(&(_, opener)) if !is_bidi_isolate(opener) => {
+                    open.pop();
+                }
+                _ => unbalanced.push(offset),
+            },
+            '\u{2069}' => {
+                if open.iter().any(|&(_, opener)| is_bidi_isolate(opener)) {
+                    // A PDI also terminates any embeddings opened inside its isolate
+                    while let Some((idx, opener)) = open.pop() {
+                        if is_bidi_isolate(opener) {
+                            break;
+                        }
+                        unbalanced.push(idx);
+                    }
+                } else {
+                    unbalanced.push(offset);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    unbalanced.extend(open.into_iter().map(|(offset, _)| offset));
+    unbalanced
+}
+
+pub fn is_tag_char(ch: char) -> bool {
+    ('\u{E0000}'..='\u{E007F}').contains(&ch)
+}
+
+// Subdivision flags (e.g. England) are WAVING BLACK FLAG followed by two to
+// six tag letters or digits spelling the region and a CANCEL TAG terminator.
+// Any other tag run after the flag is hidden text, not part of the emoji.
+pub fn is_emoji_flag_tags(previous: Option<char>, tags: impl IntoIterator<Item = char>) -> bool {
+    if previous != Some('\u{1F3F4}') {
+        return false;
+    }
+    let tags: Vec<char> = tags.into_iter().collect();
+    match tags.split_last() {
+        Some(('\u{E007F}', spec)) => {
+            (2..=6).contains(&spec.len())
+                && spec
+                    .iter()
+                    .all(|ch| matches!(ch, '\u{E0030}'..='\u{E0039}' | '\u{E0061}'..='\u{E007A}'))
+        }
+        _ => false,
+    }
+}
+
+// Scripts whose spelling relies on ZWJ and ZWNJ to control how letters join
+const JOINING_SCRIPTS: &[&str] = &[
+    "Arabic",
+    "Syriac",
+    "Nko",
+    "Mongolian",
+    "Devanagari",
+    "Bengali",
+    "Gurmukhi",
+    "Gujarati",
+    "Oriya",
+    "Tamil",
+    "Telugu",
+    "Kannada",
+    "Malayalam",
+    "Sinhala",
+];
+
+// Emoji, skin tone modifiers and the emoji presentation selector can all end
+// the part of an emoji sequence before a ZWJ
+const EMOJI_BEFORE_ZWJ: &[&str] = &["Extended_Pictographic", "Emoji_Modifier", "U+FE0F"];
+const EMOJI_AFTER_ZWJ: &[&str] = &["Extended_Pictographic"];
+const COMBINING_MARKS: &[&str] = &["Mn", "Me"];
+
+fn is_joiner(ch: char) -> bool {
+    matches!(ch, '\u{200C}' | '\u{200D}')
+}
+
+// ZWJ and ZWNJ are kept after a letter of a joining script unless a letter
+// of another script follows, and ZWJ is kept between the parts of an emoji ZWJ
+// sequence such as 👨‍👩‍👧. Anywhere else they are stray. Combining marks such
+// as Arabic harakat belong to every script, so the letter they sit on decides.
+fn is_meaningful_joiner(joiner: char, before: &str, next: Option<char>) -> bool {
+    static JOINING: OnceLock<CharSet> = OnceLock::new();
+    static BEFORE_ZWJ: OnceLock<CharSet> = OnceLock::new();
+    static AFTER_ZWJ: OnceLock<CharSet> = OnceLock::new();
+    static MARKS: OnceLock<CharSet> = OnceLock::new();
+
+    let marks = MARKS.get_or_init(|| static_char_set(COMBINING_MARKS));
+    let Some(previous) = before.chars().rev().find(|&ch| !marks.contains(ch)) else {
+        return false;
+    };
+
+    let joining = JOINING.get_or_init(|| static_char_set(JOINING_SCRIPTS));
+    if joining.contains(previous) {
+        return !next.is_some_and(|next| next.is_alphanumeric() && !joining.contains(next));
+    }
+
+    joiner == '\u{200D}'
+        && BEFORE_ZWJ
+            .get_or_init(|| static_char_set(EMOJI_BEFORE_ZWJ))
+            .contains(previous)
+        && next.is_some_and(|next| {
+            AFTER_ZWJ
+                .get_or_init(|| static_char_set(EMOJI_AFTER_ZWJ))
+                .contains(next)
+        })
+}
+
+fn static_char_set(entries: &[&str]) -> CharSet {
+    let entries = entries
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>();
+    CharSet::try_from(entries).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directives::find_suppressions;
+    use std::path::Path;
+
+    fn targets() -> TargetCharacters {
+        TargetCharacters::default()
+    }
+
+    fn clean(targets: &TargetCharacters, line_endings: LineEndings, content: &str) -> String {
+        let suppressed = find_suppressions(Path::new("test.txt"), content);
+        Cleaner::new(targets, line_endings, BomPolicy::Strip)
+            .clean(content, &suppressed)
+            .content
+            .into_owned()
+    }
+
+    // The pass-per-target cleaner the lookup engine replaced, kept as a
+    // reference for the targets both support
+    fn multi_pass(targets: &TargetCharacters, line_endings: LineEndings, content: &str) -> String {
+        let mut result = content.to_string();
+        if targets.zero_width_spaces {
+            result = result.replace(['\u{200B}', '\u{200C}', '\u{200D}', '\u{FEFF}'], "");
+        }
+        if targets.non_breaking_spaces {
+            result = result.replace('\u{00A0}', " ");
+        }
+        if targets.control_characters {
+            result = result
+                .chars()
+                .filter(|&ch| matches!(ch, '\n' | '\r' | '\t') || !ch.is_ascii_control())
+                .collect();
+        }
+        if targets.unicode_whitespace {
+            result = result
+                .chars()
+                .filter(|&ch| matches!(ch, ' ' | '\n' | '\r' | '\t') || !ch.is_whitespace())
+                .collect();
+        }
+        if targets.trailing_whitespace {
+            result = map_lines(&result, |line| line.trim_end());
+        }
+        result = result
+            .chars()
+            .filter(|&ch| !targets.custom_chars.contains(ch))
+            .collect();
+        result = map_lines(
+            &result,
+            |line| if line.trim().is_empty() { "" } else { line },
+        );
+        match line_endings {
+            LineEndings::Preserve => result,
+            LineEndings::Lf => result.replace("\r\n", "\n"),
+            LineEndings::Crlf => result.replace("\r\n", "\n").replace('\n', "\r\n"),
+        }
+    }
+
+    fn map_lines<'a>(content: &'a str, f: impl Fn(&'a str) -> &'a str) -> String {
+        let mut result = String::with_capacity(content.len());
+        for line in content.split_inclusive('\n') {
+            let (body, ending) = split_line_ending(line);
+            result.push_str(f(body));
+            result.push_str(ending);
+        }
+        result
+    }
+
+    fn assert_matches_multi_pass(targets: &TargetCharacters, content: &str) {
+        for line_endings in [LineEndings::Preserve, LineEndings::Lf, LineEndings::Crlf] {
+            assert_eq!(
+                clean(targets, line_endings, content),
+                multi_pass(targets, line_endings, content),
+                "{content:?} with {line_endings:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn matches_multi_pass_on_crlf_content() {
+        let content = "a\u{200B}b  \r\nc\u{00A0}d\r\n \t \r\nlast\u{2003}";
+        assert_matches_multi_pass(&targets(), content);
+        assert_matches_multi_pass(&targets(), "mixed\r\nendings\nhere\r\n");
+    }
+
+    #[test]
+    fn matches_multi_pass_replacing_nbsp() {
+        assert_matches_multi_pass(&targets(), "x\u{00A0}y\u{00A0}\u{00A0}z\n");
+        let content = "trailing\u{00A0}\nleading \u{00A0}\n";
+        assert_matches_multi_pass(&targets(), content);
+        assert_eq!(
+            clean(&targets(), LineEndings::Preserve, "x\u{00A0}y"),
+            "x y"
+        );
+    }
+
+    #[test]
+    fn matches_multi_pass_on_whitespace_only_lines() {
+        let content = "a\n \t \nb\n\u{3000}\n\u{200B}\n\u{0007}  \n";
+        assert_matches_multi_pass(&targets(), content);
+
+        let mut keep_blank = targets();
+        keep_blank.whitespace_only_lines = false;
+        keep_blank.trailing_whitespace = false;
+        assert_eq!(
+            clean(&keep_blank, LineEndings::Preserve, "a\n  \n"),
+            "a\n  \n"
+        );
+    }
+
+    #[test]
+    fn matches_multi_pass_trimming_after_removed_characters() {
+        let mut custom = targets();
+        custom.custom_chars = CharSet::try_from(vec!["U+2060".to_string()]).unwrap();
+        let content = "a \u{200B}\nb\t\u{0007}\nc\u{2060}\nd\u{2003} \u{00A0}\n";
+        assert_matches_multi_pass(&custom, content);
+    }
+
+    #[test]
+    fn removes_custom_characters_before_trimming() {
+        let mut custom = targets();
+        custom.custom_chars = CharSet::try_from(vec!["U+2060".to_string()]).unwrap();
+        let content = "a \u{2060}\n";
+        assert_eq!(clean(&custom, LineEndings::Preserve, content), "a\n");
+        // Multi-pass trimmed first and left the space the character hid
+        assert_eq!(multi_pass(&custom, LineEndings::Preserve, content), "a \n");
+    }
+
+    #[test]
+    fn matches_multi_pass_with_custom_characters() {
+        let mut custom = targets();
+        custom.custom_chars = CharSet::try_from(vec![
+            "U+2060".to_string(),
+            "x".to_string(),
+            "Private Use Area".to_string(),
+        ])
+        .unwrap();
+        let content = "ax\u{2060}b\u{E000}c\u{F8FF}\r\nxxx\n";
+        assert_matches_multi_pass(&custom, content);
+    }
+
+    #[test]
+    fn matches_multi_pass_with_targets_disabled() {
+        let mut off = targets();
+        off.zero_width_spaces = false;
+        off.non_breaking_spaces = false;
+        off.unicode_whitespace = false;
+        let content = "a\u{200B}b\u{00A0}c\u{2003} \n";
+        assert_matches_multi_pass(&off, content);
+    }
+
+    #[test]
+    fn borrows_clean_ascii_content() {
+        let content = "fn main() {\n\tprintln!(\"hi\");\n}\n";
+        let suppressed = find_suppressions(Path::new("test.txt"), content);
+        let output = Cleaner::new(&targets(), LineEndings::Preserve, BomPolicy::Strip)
+            .clean(content, &suppressed);
+        assert!(matches!(output.content, Cow::Borrowed(_)));
+        assert!(output.edits.is_empty());
+    }
+
+    #[test]
+    fn cleans_ascii_content_that_needs_changes() {
+        assert_matches_multi_pass(&targets(), "a  \nb\x07c\n   \nd\r\n");
+        assert_eq!(clean(&targets(), LineEndings::Preserve, "a  \n"), "a\n");
+    }
+
+    #[test]
+    fn reports_one_edit_per_changed_character() {
+        let content = "a\u{200B}\u{00A0}b \n";
+        let suppressed = find_suppressions(Path::new("test.txt"), content);
+        let output = Cleaner::new(&targets(), LineEndings::Preserve, BomPolicy::Strip)
+            .clean(content, &suppressed);
+        let categories: Vec<Category> = output.edits.iter().map(|edit| edit.category).collect();
+        assert_eq!(
+            categories,
+            [
+                Category::ZeroWidthSpace,
+                Category::NonBreakingSpace,
+                Category::TrailingWhitespace
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_nbsp_to_its_own_target() {
+        let mut keep_nbsp = targets();
+        keep_nbsp.non_breaking_spaces = false;
+        assert_eq!(
+            clean(&keep_nbsp, LineEndings::Preserve, "a\u{00A0}b\u{2003}c\n"),
+            "a\u{00A0}bc\n"
+        );
+        // Multi-pass removed it as Unicode whitespace instead
+        assert_eq!(
+            multi_pass(&keep_nbsp, LineEndings::Preserve, "a\u{00A0}b\u{2003}c\n"),
+            "abc\n"
+        );
+    }
+
+    #[test]
+    fn finds_unbalanced_bidi_controls() {
+        // Closed embedding and isolate
+        assert!(unbalanced_bidi_offsets("a\u{202E}b\u{202C}c\u{2066}d\u{2069}").is_empty());
+        // Opener left open at the end of the line
+        assert_eq!(unbalanced_bidi_offsets("a\u{202E}bc"), [1]);
+        // Closers without an opener
+        assert_eq!(unbalanced_bidi_offsets("\u{202C}x\u{2069}"), [0, 4]);
+        // PDF cannot close an isolate
+        assert_eq!(unbalanced_bidi_offsets("\u{2067}\u{202C}\u{2069}"), [3]);
+        // PDI closes its isolate and the embeddings opened inside it
+        assert_eq!(unbalanced_bidi_offsets("\u{2066}\u{202B}x\u{2069}"), [3]);
+    }
+
+    #[test]
+    fn strips_directional_marks_unless_disabled() {
+        let content = "\u{05D0}\u{200F}\u{202E}x\u{200E}\u{061C}\n";
+        assert_eq!(
+            clean(&targets(), LineEndings::Preserve, content),
+            "\u{05D0}x\n"
+        );
+
+        let mut keep_marks = targets();
+        keep_marks.directional_marks = false;
+        assert_eq!(
+            clean(&keep_marks, LineEndings::Preserve, content),
+            "\u{05D0}\u{200F}x\u{200E}\u{061C}\n"
+        );
+    }
+
+    #[test]
+    fn removes_attacks_on_suppressed_lines() {
+        let content = "a\u{200B}\u{202E}b\u{E0041} // ghost-scrub: ignore-line\n";
+        assert_eq!(
+            clean(&targets(), LineEndings::Preserve, content),
+            "a\u{200B}b // ghost-scrub: ignore-line\n"
+        );
+
+        let mut suppress_all = targets();
+        suppress_all.directives_suppress_bidi_and_tags = true;
+        assert_eq!(
+            clean(&suppress_all, LineEndings::Preserve, content),
+            content
+        );
+    }
+
+    #[test]
+    fn keeps_only_subdivision_flag_tags() {
+        let tags = |text: &str| -> String {
+            text.chars()
+                .map(|ch| char::from_u32(0xE0000 + ch as u32).unwrap())
+                .collect()
+        };
+        let england = format!("\u{1F3F4}{}\u{E007F}", tags("gbeng"));
+        assert!(is_emoji_flag_tags(
+            Some('\u{1F3F4}'),
+            england.chars().skip(1)
+        ));
+
+        let hidden = format!("\u{1F3F4}{}\u{E007F}", tags("Ignore all previous"));
+        assert!(!is_emoji_flag_tags(
+            Some('\u{1F3F4}'),
+            hidden.chars().skip(1)
+        ));
+        assert_eq!(
+            clean(&targets(), LineEndings::Preserve, &hidden),
+            "\u{1F3F4}"
+        );
+    }
+
+    #[test]
+    fn keeps_joiners_in_joining_scripts() {
+        // Persian ZWNJ, also after a letter carrying a fatha
+        for word in [
+            "\u{0645}\u{06CC}\u{200C}\u{062E}\u{0648}\u{0627}\u{0645}",
+            "\u{0645}\u{064E}\u{200C}\u{062E}",
+        ] {
+            assert_eq!(clean(&targets(), LineEndings::Preserve, word), word);
+        }
+        // Devanagari half form and Malayalam chillu
+        for word in [
+            "\u{0915}\u{094D}\u{200D}\u{0937}",
+            "\u{0D28}\u{0D4D}\u{200D}",
+        ] {
+            assert_eq!(clean(&targets(), LineEndings::Preserve, word), word);
+        }
+    }
+
+    #[test]
+    fn keeps_zwj_in_emoji_sequences() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let rainbow_flag = "\u{1F3F3}\u{FE0F}\u{200D}\u{1F308}";
+        let skin_tone = "\u{1F469}\u{1F3FD}\u{200D}\u{1F4BB}";
+        for sequence in [family, rainbow_flag, skin_tone] {
+            assert_eq!(clean(&targets(), LineEndings::Preserve, sequence), sequence);
+        }
+    }
+
+    #[test]
+    fn removes_stray_joiners() {
+        let cases = [
+            ("a\u{200D}b", "ab"),
+            ("\u{200C}x", "x"),
+            // A mark does not make a Latin letter part of a joining script
+            ("e\u{0301}\u{200C}f", "e\u{0301}f"),
+            // ZWNJ is not part of emoji sequences
+            ("\u{1F468}\u{200C}\u{1F469}", "\u{1F468}\u{1F469}"),
+            // A joining letter followed by a Latin one
+            ("\u{0645}\u{200C}a", "\u{0645}a"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(clean(&targets(), LineEndings::Preserve, input), expected);
+        }
+    }
+}
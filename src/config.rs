@@ -1,29 +1,93 @@
+use crate::charset::CharSet;
+use crate::discovery;
+use crate::encoding::encoding_for_label;
+use encoding_rs::Encoding;
+use glob::Pattern;
 use serde::{Deserialize, Serialize};
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::{Spanned, Table, Value};
+
+pub const CONFIG_FILE_NAME: &str = ".ghostscrub";
+
+// Keys that control how config files are combined rather than how files are cleaned
+pub const ROOT_KEY: &str = "root";
+const EXTENDS_KEY: &str = "extends";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct GhostScrubConfig {
+    // Stops config discovery from looking in parent directories
+    #[serde(default, skip_serializing)]
+    pub root: bool,
+
+    // Another config file to load first, relative to this one
+    #[serde(default, skip_serializing)]
+    pub extends: Option<String>,
+
     #[serde(default = "default_include_extensions")]
     pub include_extensions: Vec<String>,
 
     #[serde(default)]
     pub exclude_extensions: Vec<String>,
 
+    // Exact file names, for files such as Makefile that have no extension
+    #[serde(default = "default_include_filenames")]
+    pub include_filenames: Vec<String>,
+
+    #[serde(default)]
+    pub exclude_filenames: Vec<String>,
+
     #[serde(default = "default_include_patterns")]
     pub include_patterns: Vec<String>,
 
     #[serde(default)]
     pub exclude_patterns: Vec<String>,
 
+    #[serde(default = "default_true")]
+    pub respect_gitignore: bool,
+
     #[serde(default = "default_target_chars")]
     pub target_characters: TargetCharacters,
 
+    #[serde(default = "default_line_endings")]
+    pub line_endings: LineEndings,
+
+    // Unset means strip when zero_width_spaces is enabled and keep otherwise
+    #[serde(default)]
+    pub bom: Option<BomPolicy>,
+
+    // Encoding for files without a byte order mark that are not valid UTF-8
+    #[serde(default)]
+    pub fallback_encoding: Option<String>,
+
+    // Files larger than this many bytes are skipped; 0 means no limit
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+
+    #[serde(default = "default_markdown")]
+    pub markdown: MarkdownMode,
+
     #[serde(default = "default_verbosity")]
     pub verbosity: VerbosityLevel,
+
+    #[serde(default)]
+    pub overrides: Vec<Override>,
+
+    // Directory that include, exclude and override patterns are relative to;
+    // the current directory when unset
+    #[serde(skip)]
+    pub root_dir: Option<PathBuf>,
+
+    // Every config file that was read to build this config
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct TargetCharacters {
     #[serde(default = "default_true")]
     pub zero_width_spaces: bool,
@@ -40,8 +104,129 @@ pub struct TargetCharacters {
     #[serde(default = "default_true")]
     pub trailing_whitespace: bool,
 
+    #[serde(default = "default_true")]
+    pub whitespace_only_lines: bool,
+
+    #[serde(default = "default_true")]
+    pub tag_characters: bool,
+
+    #[serde(default = "default_bidi_controls")]
+    pub bidi_controls: BidiControlMode,
+
+    // LRM, RLM and ALM, removed along with the other controls under strip
+    #[serde(default = "default_true")]
+    pub directional_marks: bool,
+
     #[serde(default)]
-    pub custom_chars: Vec<String>,
+    pub custom_chars: CharSet,
+
+    // Characters that are never removed, whichever target would match them
+    #[serde(default)]
+    pub allow_chars: CharSet,
+
+    // Whether inline directives may also exempt bidi controls and tag
+    // characters, which otherwise are removed even on suppressed lines
+    #[serde(default)]
+    pub directives_suppress_bidi_and_tags: bool,
+}
+
+// Settings applied on top of the base config for files matching `files`.
+// Later overrides win over earlier ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    pub files: Vec<String>,
+
+    pub include_extensions: Option<Vec<String>>,
+
+    pub exclude_extensions: Option<Vec<String>>,
+
+    pub include_filenames: Option<Vec<String>>,
+
+    pub exclude_filenames: Option<Vec<String>>,
+
+    #[serde(default)]
+    pub target_characters: TargetOverrides,
+
+    pub line_endings: Option<LineEndings>,
+
+    pub bom: Option<BomPolicy>,
+
+    pub fallback_encoding: Option<String>,
+
+    pub max_file_size: Option<u64>,
+
+    pub markdown: Option<MarkdownMode>,
+
+    pub verbosity: Option<VerbosityLevel>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetOverrides {
+    pub zero_width_spaces: Option<bool>,
+    pub non_breaking_spaces: Option<bool>,
+    pub control_characters: Option<bool>,
+    pub unicode_whitespace: Option<bool>,
+    pub trailing_whitespace: Option<bool>,
+    pub whitespace_only_lines: Option<bool>,
+    pub tag_characters: Option<bool>,
+    pub bidi_controls: Option<BidiControlMode>,
+    pub directional_marks: Option<bool>,
+    pub custom_chars: Option<CharSet>,
+    pub allow_chars: Option<CharSet>,
+    pub directives_suppress_bidi_and_tags: Option<bool>,
+}
+
+// The settings that can differ between files once overrides are applied
+#[derive(Debug, Clone)]
+pub struct FileSettings {
+    pub include_extensions: Vec<String>,
+    pub exclude_extensions: Vec<String>,
+    pub include_filenames: Vec<String>,
+    pub exclude_filenames: Vec<String>,
+    pub target_characters: TargetCharacters,
+    pub line_endings: LineEndings,
+    pub bom: Option<BomPolicy>,
+    pub fallback_encoding: Option<&'static Encoding>,
+    pub max_file_size: u64,
+    pub markdown: MarkdownMode,
+    pub verbosity: VerbosityLevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BidiControlMode {
+    Strip,
+    Unbalanced,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    Preserve,
+    Lf,
+    Crlf,
+}
+
+// What happens to a byte order mark at the start of a file. A U+FEFF anywhere
+// else is a zero width space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BomPolicy {
+    Strip,
+    Keep,
+    Add,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkdownMode {
+    // Keep hard line breaks and NBSP in prose, clean code blocks by language
+    Aware,
+    // Clean Markdown like any other text file
+    Plain,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,10 +242,23 @@ impl Default for GhostScrubConfig {
         Self {
             include_extensions: default_include_extensions(),
             exclude_extensions: Vec::new(),
+            include_filenames: default_include_filenames(),
+            exclude_filenames: Vec::new(),
             include_patterns: default_include_patterns(),
             exclude_patterns: default_exclude_patterns(),
+            respect_gitignore: true,
             target_characters: default_target_chars(),
+            line_endings: default_line_endings(),
+            bom: None,
+            fallback_encoding: None,
+            max_file_size: default_max_file_size(),
+            markdown: default_markdown(),
             verbosity: default_verbosity(),
+            overrides: Vec::new(),
+            root: false,
+            extends: None,
+            root_dir: None,
+            sources: Vec::new(),
         }
     }
 }
@@ -71,6 +269,24 @@ impl Default for TargetCharacters {
     }
 }
 
+impl Default for BidiControlMode {
+    fn default() -> Self {
+        default_bidi_controls()
+    }
+}
+
+impl Default for LineEndings {
+    fn default() -> Self {
+        default_line_endings()
+    }
+}
+
+impl Default for MarkdownMode {
+    fn default() -> Self {
+        default_markdown()
+    }
+}
+
 impl Default for VerbosityLevel {
     fn default() -> Self {
         default_verbosity()
@@ -97,6 +313,7 @@ fn default_include_extensions() -> Vec<String> {
         "swift".to_string(),
         "kt".to_string(),
         "scala".to_string(),
+        "sh".to_string(),
         "clj".to_string(),
         "hs".to_string(),
         "ml".to_string(),
@@ -113,6 +330,30 @@ fn default_include_extensions() -> Vec<String> {
     ]
 }
 
+fn default_include_filenames() -> Vec<String> {
+    vec![
+        "Makefile".to_string(),
+        "makefile".to_string(),
+        "GNUmakefile".to_string(),
+        "Dockerfile".to_string(),
+        "Containerfile".to_string(),
+        "Jenkinsfile".to_string(),
+        "Vagrantfile".to_string(),
+        "Gemfile".to_string(),
+        "Rakefile".to_string(),
+        "Procfile".to_string(),
+        "Justfile".to_string(),
+        "justfile".to_string(),
+        "CODEOWNERS".to_string(),
+        "LICENSE".to_string(),
+        "README".to_string(),
+        ".gitignore".to_string(),
+        ".gitattributes".to_string(),
+        ".dockerignore".to_string(),
+        ".editorconfig".to_string(),
+    ]
+}
+
 fn default_include_patterns() -> Vec<String> {
     vec!["**/*".to_string()]
 }
@@ -168,10 +409,32 @@ fn default_target_chars() -> TargetCharacters {
         control_characters: true,
         unicode_whitespace: true,
         trailing_whitespace: true,
-        custom_chars: Vec::new(),
+        whitespace_only_lines: true,
+        tag_characters: true,
+        bidi_controls: default_bidi_controls(),
+        directional_marks: true,
+        custom_chars: CharSet::default(),
+        allow_chars: CharSet::default(),
+        directives_suppress_bidi_and_tags: false,
     }
 }
 
+fn default_bidi_controls() -> BidiControlMode {
+    BidiControlMode::Strip
+}
+
+fn default_line_endings() -> LineEndings {
+    LineEndings::Preserve
+}
+
+fn default_max_file_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_markdown() -> MarkdownMode {
+    MarkdownMode::Aware
+}
+
 fn default_verbosity() -> VerbosityLevel {
     VerbosityLevel::Normal
 }
@@ -182,17 +445,85 @@ fn default_true() -> bool {
 
 impl GhostScrubConfig {
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        let config: GhostScrubConfig = toml::from_str(&content)?;
+        let mut sources = Vec::new();
+        let loaded = load_table(path.as_ref(), &mut sources)?;
+        let mut config = Self::from_table(loaded.table)?;
+        config.sources = sources;
         Ok(config)
     }
 
-    pub fn load_default() -> Self {
-        Self::load_from_file(".ghostscrub").unwrap_or_default()
+    // Discovers .ghostscrub files from the current directory up to the
+    // repository root and in subdirectories below it
+    pub fn load_default() -> Result<Self, Box<dyn std::error::Error>> {
+        discovery::discover(&env::current_dir()?)
+    }
+
+    pub fn from_table(table: Table) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Value::Table(table).try_into()?)
+    }
+
+    pub fn project_root(&self) -> PathBuf {
+        self.root_dir
+            .clone()
+            .unwrap_or_else(|| env::current_dir().unwrap_or_default())
     }
 
-    pub fn should_process_file(&self, file_path: &Path) -> bool {
-        if let Some(extension) = file_path.extension().and_then(|ext| ext.to_str()) {
+    // The settings for a file matched by the overrides at `indices`
+    pub fn settings_for(&self, indices: &[usize]) -> FileSettings {
+        let mut settings = FileSettings {
+            include_extensions: self.include_extensions.clone(),
+            exclude_extensions: self.exclude_extensions.clone(),
+            include_filenames: self.include_filenames.clone(),
+            exclude_filenames: self.exclude_filenames.clone(),
+            target_characters: self.target_characters.clone(),
+            line_endings: self.line_endings,
+            bom: self.bom,
+            fallback_encoding: self
+                .fallback_encoding
+                .as_deref()
+                .and_then(encoding_for_label),
+            max_file_size: self.max_file_size,
+            markdown: self.markdown,
+            verbosity: self.verbosity.clone(),
+        };
+        for override_config in indices.iter().filter_map(|&idx| self.overrides.get(idx)) {
+            override_config.apply(&mut settings);
+        }
+        settings
+    }
+}
+
+impl FileSettings {
+    // A byte order mark is a zero-width character, so it is only stripped by
+    // default when those are
+    pub fn bom(&self) -> BomPolicy {
+        self.bom
+            .unwrap_or(if self.target_characters.zero_width_spaces {
+                BomPolicy::Strip
+            } else {
+                BomPolicy::Keep
+            })
+    }
+
+    // Named files are decided by their name alone. Anything else goes by the
+    // extension of `language_path`, which for scripts comes from the shebang;
+    // files with no extension even then only pass when every extension does.
+    pub fn should_process_file(&self, file_path: &Path, language_path: &Path) -> bool {
+        let file_name = file_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        if self.exclude_filenames.iter().any(|name| name == file_name) {
+            return false;
+        }
+        if self.include_filenames.iter().any(|name| name == file_name) {
+            return true;
+        }
+
+        let Some(extension) = language_path.extension() else {
+            return self.include_extensions.is_empty();
+        };
+        if let Some(extension) = extension.to_str() {
             if !self.exclude_extensions.is_empty()
                 && self.exclude_extensions.contains(&extension.to_string())
             {
@@ -209,3 +540,229 @@ impl GhostScrubConfig {
         true
     }
 }
+
+impl Override {
+    fn apply(&self, settings: &mut FileSettings) {
+        if let Some(include_extensions) = &self.include_extensions {
+            settings.include_extensions = include_extensions.clone();
+        }
+        if let Some(exclude_extensions) = &self.exclude_extensions {
+            settings.exclude_extensions = exclude_extensions.clone();
+        }
+        if let Some(include_filenames) = &self.include_filenames {
+            settings.include_filenames = include_filenames.clone();
+        }
+        if let Some(exclude_filenames) = &self.exclude_filenames {
+            settings.exclude_filenames = exclude_filenames.clone();
+        }
+        self.target_characters
+            .apply(&mut settings.target_characters);
+        if let Some(line_endings) = self.line_endings {
+            settings.line_endings = line_endings;
+        }
+        if let Some(bom) = self.bom {
+            settings.bom = Some(bom);
+        }
+        if let Some(label) = &self.fallback_encoding {
+            settings.fallback_encoding = encoding_for_label(label);
+        }
+        if let Some(max_file_size) = self.max_file_size {
+            settings.max_file_size = max_file_size;
+        }
+        if let Some(markdown) = self.markdown {
+            settings.markdown = markdown;
+        }
+        if let Some(verbosity) = &self.verbosity {
+            settings.verbosity = verbosity.clone();
+        }
+    }
+}
+
+impl TargetOverrides {
+    fn apply(&self, targets: &mut TargetCharacters) {
+        let flags = [
+            (self.zero_width_spaces, &mut targets.zero_width_spaces),
+            (self.non_breaking_spaces, &mut targets.non_breaking_spaces),
+            (self.control_characters, &mut targets.control_characters),
+            (self.unicode_whitespace, &mut targets.unicode_whitespace),
+            (self.trailing_whitespace, &mut targets.trailing_whitespace),
+            (
+                self.whitespace_only_lines,
+                &mut targets.whitespace_only_lines,
+            ),
+            (self.tag_characters, &mut targets.tag_characters),
+            (self.directional_marks, &mut targets.directional_marks),
+            (
+                self.directives_suppress_bidi_and_tags,
+                &mut targets.directives_suppress_bidi_and_tags,
+            ),
+        ];
+        for (value, target) in flags {
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+
+        if let Some(bidi_controls) = self.bidi_controls {
+            targets.bidi_controls = bidi_controls;
+        }
+        if let Some(custom_chars) = &self.custom_chars {
+            targets.custom_chars = custom_chars.clone();
+        }
+        if let Some(allow_chars) = &self.allow_chars {
+            targets.allow_chars = allow_chars.clone();
+        }
+    }
+}
+
+// The values that need checking beyond their type, read with their position
+// in the file so errors can point at the exact entry
+#[derive(Deserialize)]
+struct SpannedValues {
+    #[serde(default)]
+    include_patterns: Vec<Spanned<String>>,
+    #[serde(default)]
+    exclude_patterns: Vec<Spanned<String>>,
+    fallback_encoding: Option<Spanned<String>>,
+    #[serde(default)]
+    overrides: Vec<SpannedOverride>,
+}
+
+#[derive(Deserialize)]
+struct SpannedOverride {
+    #[serde(default)]
+    files: Vec<Spanned<String>>,
+    fallback_encoding: Option<Spanned<String>>,
+}
+
+// An error message and the byte range of the value it is about
+type ValidationError = (String, Range<usize>);
+
+impl SpannedValues {
+    // Checks the values serde cannot: glob syntax and encoding labels
+    fn validate(&self) -> Result<(), ValidationError> {
+        validate_patterns("include_patterns", &self.include_patterns)?;
+        validate_patterns("exclude_patterns", &self.exclude_patterns)?;
+        validate_encoding("fallback_encoding", &self.fallback_encoding)?;
+        for (idx, override_config) in self.overrides.iter().enumerate() {
+            validate_patterns(&format!("overrides[{idx}].files"), &override_config.files)?;
+            validate_encoding(
+                &format!("overrides[{idx}].fallback_encoding"),
+                &override_config.fallback_encoding,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_patterns(key: &str, patterns: &[Spanned<String>]) -> Result<(), ValidationError> {
+    for pattern in patterns {
+        let glob = pattern.get_ref();
+        let glob = glob.strip_prefix('!').unwrap_or(glob);
+        if let Err(e) = Pattern::new(glob) {
+            return Err((
+                format!("{key}: invalid glob {:?}: {e}", pattern.get_ref()),
+                pattern.span(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_encoding(key: &str, label: &Option<Spanned<String>>) -> Result<(), ValidationError> {
+    match label {
+        Some(label) if encoding_for_label(label.get_ref()).is_none() => Err((
+            format!("{key}: unknown encoding {:?}", label.get_ref()),
+            label.span(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Prefixes a validation error with the file, line and column of its value
+fn locate_error(path: &Path, content: &str, (error, span): ValidationError) -> String {
+    let before = &content[..span.start];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    format!(
+        "{} at line {line}, column {column}: {error}",
+        path.display()
+    )
+}
+
+// A config file read as a TOML table with its `extends` chain merged in
+pub struct ConfigTable {
+    pub table: Table,
+    // Whether the file itself sets `root = true`
+    pub root: bool,
+}
+
+// Reads a config file with the extended file first and this file's keys on
+// top. Every file read is appended to `sources`.
+pub fn load_table(
+    path: &Path,
+    sources: &mut Vec<PathBuf>,
+) -> Result<ConfigTable, Box<dyn std::error::Error>> {
+    load_table_chain(path, &mut Vec::new(), sources)
+}
+
+fn load_table_chain(
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    sources: &mut Vec<PathBuf>,
+) -> Result<ConfigTable, Box<dyn std::error::Error>> {
+    let canonical = fs::canonicalize(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if chain.contains(&canonical) {
+        return Err(format!("{}: extends chain forms a cycle", path.display()).into());
+    }
+    chain.push(canonical);
+    sources.push(path.to_path_buf());
+
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    // Each file is checked on its own first, so unknown keys and type errors
+    // are reported with the line and column they appear at
+    let config: GhostScrubConfig =
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    toml::from_str::<SpannedValues>(&content)?
+        .validate()
+        .map_err(|e| locate_error(path, &content, e))?;
+
+    let mut table: Table = toml::from_str(&content)?;
+    table.remove(EXTENDS_KEY);
+    let Some(extends) = config.extends else {
+        return Ok(ConfigTable {
+            table,
+            root: config.root,
+        });
+    };
+
+    // Relative paths are resolved from the extending file's directory
+    let base_path = path.parent().unwrap_or(Path::new(".")).join(extends);
+    let mut base = load_table_chain(&base_path, chain, sources)?.table;
+    base.remove(ROOT_KEY);
+    merge_tables(&mut base, table);
+    Ok(ConfigTable {
+        table: base,
+        root: config.root,
+    })
+}
+
+// Nested tables merge key by key and other values replace the base's,
+// except `overrides`, which accumulate so the base's still apply
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (Some(Value::Array(base_array)), Value::Array(overlay_array)) if key == "overrides" => {
+                base_array.extend(overlay_array);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
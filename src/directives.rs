@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+const DIRECTIVE_PREFIX: &str = "ghost-scrub:";
+
+const ALL_COMMENT_OPENERS: &[&str] = &["//", "/*", "#", ";", "--", "{-", "(*", "<!--", "%"];
+
+// Lines that inline directives exempt from cleaning, as sorted 1-based ranges
+#[derive(Debug)]
+pub struct Suppressions {
+    ranges: Vec<RangeInclusive<usize>>,
+}
+
+impl Suppressions {
+    pub fn contains(&self, line: usize) -> bool {
+        self.ranges
+            .binary_search_by(|range| {
+                if *range.end() < line {
+                    Ordering::Less
+                } else if *range.start() > line {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    IgnoreLine,
+    IgnoreNextLine,
+    Off,
+    On,
+}
+
+// Scans comments for `ghost-scrub: ignore-line`, `ignore-next-line`, `off`
+// and `on`. Directive lines are suppressed along with what they cover, and an
+// `off` without a matching `on` runs to the end of the file.
+pub fn find_suppressions(file_path: &Path, content: &str) -> Suppressions {
+    let mut ranges: Vec<RangeInclusive<usize>> = Vec::new();
+    if !content.contains(DIRECTIVE_PREFIX) {
+        return Suppressions { ranges };
+    }
+
+    let openers = comment_openers(file_path);
+    let mut off_since = None;
+    let mut line_count = 0;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line_number = idx + 1;
+        line_count = line_number;
+
+        let Some(directive) = parse_directive(line, openers) else {
+            continue;
+        };
+        match (directive, off_since) {
+            (Directive::Off, None) => off_since = Some(line_number),
+            (Directive::On, Some(start)) => {
+                ranges.push(start..=line_number);
+                off_since = None;
+            }
+            (Directive::IgnoreLine, None) => ranges.push(line_number..=line_number),
+            (Directive::IgnoreNextLine, None) => ranges.push(line_number..=line_number + 1),
+            // Everything else is already inside an `off` region or has nothing to end
+            _ => {}
+        }
+    }
+
+    if let Some(start) = off_since {
+        ranges.push(start..=line_count);
+    }
+
+    // An ignore-next-line can overlap the following directive's range
+    ranges.sort_by_key(|range| *range.start());
+    let mut merged: Vec<RangeInclusive<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= *last.end() => {
+                *last = *last.start()..=(*last.end()).max(*range.end());
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    Suppressions { ranges: merged }
+}
+
+fn parse_directive(line: &str, openers: &[&str]) -> Option<Directive> {
+    let position = line.find(DIRECTIVE_PREFIX)?;
+
+    // Only honor directives that open a comment, not ones inside ordinary text
+    let before = line[..position].trim_end();
+    if !openers.iter().any(|opener| before.ends_with(opener)) {
+        return None;
+    }
+
+    let rest = line[position + DIRECTIVE_PREFIX.len()..].trim_start();
+    let name_len = rest
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '-'))
+        .unwrap_or(rest.len());
+
+    match &rest[..name_len] {
+        "ignore-line" => Some(Directive::IgnoreLine),
+        "ignore-next-line" => Some(Directive::IgnoreNextLine),
+        "off" => Some(Directive::Off),
+        "on" => Some(Directive::On),
+        _ => None,
+    }
+}
+
+fn comment_openers(file_path: &Path) -> &'static [&'static str] {
+    let extension = file_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "rs" | "js" | "ts" | "jsx" | "tsx" | "go" | "java" | "c" | "cpp" | "h" | "hpp" | "cs"
+        | "swift" | "kt" | "scala" | "json" => &["//", "/*"],
+        "php" => &["//", "/*", "#"],
+        "py" | "rb" | "sh" | "pl" | "ps1" | "yaml" | "yml" | "toml" | "cfg" | "conf" => &["#"],
+        "ini" => &[";", "#"],
+        "clj" => &[";"],
+        "hs" => &["--", "{-"],
+        "ml" => &["(*"],
+        "xml" => &["<!--"],
+        // Plain text and Markdown may quote any language's comments
+        _ => ALL_COMMENT_OPENERS,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suppressed_lines(file_name: &str, content: &str) -> Vec<usize> {
+        let suppressed = find_suppressions(Path::new(file_name), content);
+        (1..=content.lines().count() + 1)
+            .filter(|&line| suppressed.contains(line))
+            .collect()
+    }
+
+    #[test]
+    fn ignores_single_lines() {
+        let content = "a\nb // ghost-scrub: ignore-line\nc\n";
+        assert_eq!(suppressed_lines("test.rs", content), [2]);
+
+        let content = "// ghost-scrub: ignore-next-line\nb\nc\n";
+        assert_eq!(suppressed_lines("test.rs", content), [1, 2]);
+    }
+
+    #[test]
+    fn suppresses_off_to_on_regions() {
+        let content = "a\n# ghost-scrub: off\nb\n# ghost-scrub: on\nc\n";
+        assert_eq!(suppressed_lines("test.py", content), [2, 3, 4]);
+    }
+
+    #[test]
+    fn runs_unclosed_off_to_end_of_file() {
+        let content = "a\n// ghost-scrub: off\nb\nc";
+        assert_eq!(suppressed_lines("test.rs", content), [2, 3, 4]);
+    }
+
+    #[test]
+    fn merges_overlapping_ranges() {
+        let content = "// ghost-scrub: ignore-next-line\n// ghost-scrub: ignore-next-line\nc\nd\n";
+        assert_eq!(suppressed_lines("test.rs", content), [1, 2, 3]);
+    }
+
+    #[test]
+    fn requires_a_comment_in_the_files_language() {
+        // `#` is not a comment in Rust, and a directive in a string is not one
+        let content = "# ghost-scrub: ignore-line\nlet s = \"ghost-scrub: off\";\n";
+        assert!(suppressed_lines("test.rs", content).is_empty());
+        assert_eq!(suppressed_lines("notes.txt", content), [1]);
+    }
+
+    #[test]
+    fn ignores_unknown_directives() {
+        let content = "// ghost-scrub: offline\n// ghost-scrub: ignore\n";
+        assert!(suppressed_lines("test.rs", content).is_empty());
+    }
+}
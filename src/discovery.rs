@@ -0,0 +1,236 @@
+use crate::config::{
+    load_table, ConfigTable, GhostScrubConfig, Override, CONFIG_FILE_NAME, ROOT_KEY,
+};
+use crate::filter::PathFilter;
+use crate::walker::IGNORE_FILE_NAME;
+use glob::Pattern;
+use ignore::WalkBuilder;
+use std::path::{Component, Path, PathBuf};
+use toml::{Table, Value};
+
+// Builds the config for a run from `cwd`. The search walks upward until a
+// config with `root = true` or the repository root, and the outermost config
+// found becomes the base. Every other config, whether between the base and
+// `cwd` or in a subdirectory below it, only applies to its own subtree.
+pub fn discover(cwd: &Path) -> Result<GhostScrubConfig, Box<dyn std::error::Error>> {
+    let mut sources = Vec::new();
+    let mut found = Vec::new();
+    let mut repo_root = None;
+    for dir in cwd.ancestors() {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if path.is_file() {
+            let loaded = load_table(&path, &mut sources)?;
+            let is_root = loaded.root;
+            found.push((path, loaded));
+            if is_root {
+                break;
+            }
+        }
+        if dir.join(".git").exists() {
+            repo_root = Some(dir);
+            break;
+        }
+    }
+
+    let mut found = found.into_iter().rev();
+    let (root_dir, mut config) = match found.next() {
+        Some((path, loaded)) => {
+            let config = GhostScrubConfig::from_table(loaded.table)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            (config_dir(&path), config)
+        }
+        // Without a config, patterns are relative to the repository root so
+        // they mean the same thing from any subdirectory
+        None => (
+            repo_root.unwrap_or(cwd).to_path_buf(),
+            GhostScrubConfig::default(),
+        ),
+    };
+    config.root_dir = Some(root_dir.clone());
+
+    for (path, loaded) in found {
+        add_nested(&mut config, &root_dir, &path, loaded)?;
+    }
+    for path in nested_config_paths(cwd, &config) {
+        let loaded = load_table(&path, &mut sources)?;
+        add_nested(&mut config, &root_dir, &path, loaded)?;
+    }
+
+    config.sources = sources;
+    Ok(config)
+}
+
+fn config_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).to_path_buf()
+}
+
+// Config files in subdirectories of `cwd`, parents before children
+fn nested_config_paths(cwd: &Path, config: &GhostScrubConfig) -> Vec<PathBuf> {
+    let filter = PathFilter::new(config);
+    let respect_gitignore = config.respect_gitignore;
+
+    let walker = WalkBuilder::new(cwd)
+        .hidden(false)
+        .ignore(respect_gitignore)
+        .git_ignore(respect_gitignore)
+        .git_global(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .filter_entry(move |entry| {
+            let is_dir = entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir());
+            !(is_dir && filter.is_dir_excluded(entry.path()))
+        })
+        .build();
+
+    let mut paths: Vec<PathBuf> = walker
+        .filter_map(Result::ok)
+        .filter(|entry| entry.depth() > 1 && entry.file_name() == CONFIG_FILE_NAME)
+        .map(|entry| entry.into_path())
+        .collect();
+    paths.sort_by_key(|path| path.components().count());
+    paths
+}
+
+// Turns a config below the base into an override scoped to its directory.
+// Its patterns are rebased onto that directory and appended to the base's.
+fn add_nested(
+    config: &mut GhostScrubConfig,
+    root_dir: &Path,
+    path: &Path,
+    loaded: ConfigTable,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scope = scope_pattern(root_dir, &config_dir(path));
+    add_scoped_table(config, &scope, loaded).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(())
+}
+
+fn add_scoped_table(
+    config: &mut GhostScrubConfig,
+    scope: &str,
+    loaded: ConfigTable,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut table = loaded.table;
+    if loaded.root {
+        // Spell out every setting so nothing is inherited from the parents
+        let full = GhostScrubConfig::from_table(table)?;
+        table = match Value::try_from(full)? {
+            Value::Table(table) => table,
+            _ => Table::new(),
+        };
+    }
+    table.remove(ROOT_KEY);
+    // Walk settings cannot vary between subdirectories
+    table.remove("respect_gitignore");
+
+    if let Some(patterns) = table.remove("include_patterns") {
+        let patterns: Vec<String> = patterns.try_into()?;
+        if config.include_patterns.is_empty() {
+            config.include_patterns.push("**".to_string());
+        }
+        // The nested list replaces whatever the parents included in this subtree
+        config.include_patterns.push(rebase(scope, "!**"));
+        config
+            .include_patterns
+            .extend(patterns.iter().map(|pattern| rebase(scope, pattern)));
+    }
+
+    if let Some(patterns) = table.remove("exclude_patterns") {
+        let patterns: Vec<String> = patterns.try_into()?;
+        config
+            .exclude_patterns
+            .extend(patterns.iter().map(|pattern| rebase(scope, pattern)));
+    }
+
+    let nested_overrides: Vec<Override> = match table.remove("overrides") {
+        Some(overrides) => overrides.try_into()?,
+        None => Vec::new(),
+    };
+
+    table.insert(
+        "files".to_string(),
+        Value::Array(vec![Value::String(rebase(scope, "**"))]),
+    );
+    let scoped: Override = Value::Table(table).try_into()?;
+    config.overrides.push(scoped);
+
+    for mut nested in nested_overrides {
+        nested.files = nested
+            .files
+            .iter()
+            .map(|pattern| rebase(scope, pattern))
+            .collect();
+        config.overrides.push(nested);
+    }
+
+    Ok(())
+}
+
+// The glob-escaped path of `dir` relative to `root_dir`
+fn scope_pattern(root_dir: &Path, dir: &Path) -> String {
+    let relative = dir.strip_prefix(root_dir).unwrap_or(dir);
+    relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(Pattern::escape(&name.to_string_lossy())),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn rebase(scope: &str, pattern: &str) -> String {
+    let (negation, pattern) = match pattern.strip_prefix('!') {
+        Some(rest) => ("!", rest),
+        None => ("", pattern),
+    };
+    let pattern = pattern.trim_start_matches('/');
+
+    if scope.is_empty() {
+        format!("{negation}{pattern}")
+    } else {
+        format!("{negation}{scope}/{pattern}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ghost-scrub-discovery-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn roots_at_the_repository_without_a_config() {
+        let repo = scratch_dir("no-config");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/nested")).unwrap();
+
+        let config = discover(&repo.join("src/nested")).unwrap();
+        assert_eq!(config.project_root(), repo);
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn roots_at_the_outermost_config() {
+        let repo = scratch_dir("config");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("packages/web")).unwrap();
+        fs::write(repo.join("packages").join(CONFIG_FILE_NAME), "").unwrap();
+        fs::write(repo.join("packages/web").join(CONFIG_FILE_NAME), "").unwrap();
+
+        let config = discover(&repo.join("packages/web")).unwrap();
+        assert_eq!(config.project_root(), repo.join("packages"));
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
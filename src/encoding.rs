@@ -0,0 +1,288 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+// A file's text along with what is needed to write it back in the encoding
+// it was read in
+pub struct DecodedFile {
+    pub text: String,
+    encoding: &'static Encoding,
+    // UTF-16 files keep the byte order mark that identified them
+    bom: bool,
+}
+
+impl DecodedFile {
+    // The bom setting only governs UTF-8 files; in other encodings the byte
+    // order mark is part of the encoding rather than the text
+    pub fn is_utf8(&self) -> bool {
+        self.encoding == UTF_8
+    }
+
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        if self.is_utf8() {
+            return Ok(text.as_bytes().to_vec());
+        }
+
+        let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+        if is_utf16(self.encoding) {
+            let big_endian = self.encoding == UTF_16BE;
+            let mut bytes = Vec::with_capacity((text.len() + 1) * 2);
+            let units = self.bom.then_some(0xFEFF).into_iter();
+            for unit in units.chain(text.encode_utf16()) {
+                if big_endian {
+                    bytes.extend(unit.to_be_bytes());
+                } else {
+                    bytes.extend(unit.to_le_bytes());
+                }
+            }
+            return Ok(bytes);
+        }
+
+        let (bytes, _, unmappable) = self.encoding.encode(text);
+        if unmappable {
+            return Err(format!(
+                "cleaned text cannot be written back as {}",
+                self.encoding.name()
+            ));
+        }
+        Ok(bytes.into_owned())
+    }
+}
+
+// Decodes a file by its byte order mark, as UTF-8 when it has none, and
+// otherwise with `fallback`. Bytes that are invalid in the chosen encoding are
+// an error rather than being replaced, so writing back cannot corrupt them.
+pub fn decode(bytes: Vec<u8>, fallback: Option<&'static Encoding>) -> Result<DecodedFile, String> {
+    match Encoding::for_bom(&bytes) {
+        // A UTF-8 byte order mark stays in the text for the bom setting to handle
+        Some((encoding, _)) if encoding == UTF_8 => {}
+        Some((encoding, bom_len)) => return decode_as(&bytes, encoding, bom_len),
+        // UTF-16 without a byte order mark is full of NUL bytes, which UTF-8
+        // text never contains
+        None => match fallback {
+            Some(fallback) if is_utf16(fallback) && bytes.contains(&0) => {
+                return decode_as(&bytes, fallback, 0)
+            }
+            _ => {}
+        },
+    }
+
+    // UTF-8 takes over the buffer that was read instead of copying it
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(DecodedFile {
+            text,
+            encoding: UTF_8,
+            bom: false,
+        }),
+        Err(e) => match fallback {
+            Some(fallback) if fallback != UTF_8 && Encoding::for_bom(e.as_bytes()).is_none() => {
+                decode_as(e.as_bytes(), fallback, 0)
+            }
+            _ => Err(
+                "not valid UTF-8; set fallback_encoding to read files in a legacy encoding"
+                    .to_string(),
+            ),
+        },
+    }
+}
+
+fn decode_as(
+    bytes: &[u8],
+    encoding: &'static Encoding,
+    bom_len: usize,
+) -> Result<DecodedFile, String> {
+    let text = encoding
+        .decode_without_bom_handling_and_without_replacement(&bytes[bom_len..])
+        .ok_or_else(|| format!("not valid {}", encoding.name()))?
+        .into_owned();
+
+    Ok(DecodedFile {
+        text,
+        encoding,
+        bom: bom_len > 0,
+    })
+}
+
+// How much of a file is inspected to decide whether it is binary
+const BINARY_SNIFF_LEN: usize = 8000;
+// Share of control characters, in percent, above which a file is binary
+const BINARY_CONTROL_PERCENT: usize = 10;
+
+// Sniffs the start of a file: a high share of control characters, or a NUL
+// byte in content that is not UTF-8, means binary. Files read as UTF-16 are
+// full of NUL bytes, so they are checked by code unit instead.
+pub fn looks_binary(bytes: &[u8], fallback: Option<&'static Encoding>) -> bool {
+    let utf16 = match Encoding::for_bom(bytes) {
+        Some((encoding, bom_len)) => is_utf16(encoding).then_some((encoding, bom_len)),
+        // Mirrors `decode`, which only reads NUL-free files as UTF-8
+        None => fallback
+            .filter(|&fallback| is_utf16(fallback) && bytes.contains(&0))
+            .map(|fallback| (fallback, 0)),
+    };
+    if let Some((encoding, bom_len)) = utf16 {
+        return looks_binary_utf16(&bytes[bom_len..], encoding == UTF_16BE);
+    }
+
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    let controls = sample
+        .iter()
+        .filter(|&&byte| is_binary_control(byte.into()))
+        .count();
+    if controls * 100 > sample.len() * BINARY_CONTROL_PERCENT {
+        return true;
+    }
+    // A stray NUL in otherwise valid UTF-8 text is a finding to clean, but
+    // next to bytes that are not UTF-8 it marks a binary format
+    sample.contains(&0) && std::str::from_utf8(sample).is_err_and(|e| e.error_len().is_some())
+}
+
+// UTF-16 text has an even length, well-formed surrogate pairs and, like any
+// text, no U+0000
+fn looks_binary_utf16(bytes: &[u8], big_endian: bool) -> bool {
+    if !bytes.len().is_multiple_of(2) {
+        return true;
+    }
+
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    let mut units: Vec<u16> = sample
+        .chunks_exact(2)
+        .map(|pair| {
+            let pair = [pair[0], pair[1]];
+            if big_endian {
+                u16::from_be_bytes(pair)
+            } else {
+                u16::from_le_bytes(pair)
+            }
+        })
+        .collect();
+    // The sample may end halfway through a surrogate pair
+    if sample.len() < bytes.len()
+        && units
+            .last()
+            .is_some_and(|unit| (0xD800..0xDC00).contains(unit))
+    {
+        units.pop();
+    }
+
+    let mut controls = 0;
+    for ch in char::decode_utf16(units.iter().copied()) {
+        match ch {
+            Ok('\0') | Err(_) => return true,
+            Ok(ch) if is_binary_control(ch.into()) => controls += 1,
+            Ok(_) => {}
+        }
+    }
+    controls * 100 > units.len() * BINARY_CONTROL_PERCENT
+}
+
+// Tabs, line breaks, form feeds and escape sequences are common in text
+fn is_binary_control(unit: u32) -> bool {
+    (unit < 0x20 && !matches!(unit, 0x09 | 0x0A | 0x0D | 0x0C | 0x1B)) || unit == 0x7F
+}
+
+fn is_utf16(encoding: &'static Encoding) -> bool {
+    encoding == UTF_16LE || encoding == UTF_16BE
+}
+
+pub fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::WINDOWS_1252;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn detects_binary_content() {
+        assert!(looks_binary(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0", None));
+        assert!(looks_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\xff\xd8", None));
+        assert!(looks_binary(&[0x01, 0x02, 0x03, b'a', b'b'], None));
+    }
+
+    #[test]
+    fn treats_text_with_a_stray_nul_as_text() {
+        let text = format!(
+            "fn main() {{\n    let s = \"a\0b\";\n}}\n{}",
+            "// x\n".repeat(10)
+        );
+        assert!(!looks_binary(text.as_bytes(), None));
+        assert!(!looks_binary(
+            "plain text\twith tabs\x0c\n".as_bytes(),
+            None
+        ));
+    }
+
+    #[test]
+    fn sniffs_utf16_by_code_unit() {
+        let text = utf16le("hello\r\nworld\n");
+        assert!(!looks_binary(&text, Some(UTF_16LE)));
+        let mut with_bom = vec![0xFF, 0xFE];
+        with_bom.extend(&text);
+        assert!(!looks_binary(&with_bom, None));
+
+        // Odd length, U+0000 and unpaired surrogates are not UTF-16 text
+        assert!(looks_binary(&[b'a', 0, b'b'], Some(UTF_16LE)));
+        assert!(looks_binary(&[b'a', 0, 0, 0, b'b', 0], Some(UTF_16LE)));
+        assert!(looks_binary(&[b'a', 0, 0x00, 0xDC], Some(UTF_16LE)));
+        let blob: Vec<u8> = (0..=255).cycle().take(1024).collect();
+        assert!(looks_binary(&blob, Some(UTF_16LE)));
+    }
+
+    #[test]
+    fn sniffs_legacy_fallback_files_by_byte() {
+        let (text, _, _) = WINDOWS_1252.encode("café\n");
+        assert!(!looks_binary(&text, Some(WINDOWS_1252)));
+        assert!(looks_binary(b"caf\xe9\0\n", Some(WINDOWS_1252)));
+    }
+
+    fn round_trip(bytes: &[u8], fallback: Option<&'static Encoding>, text: &str) {
+        let decoded = decode(bytes.to_vec(), fallback).unwrap();
+        assert_eq!(decoded.text, text);
+        assert_eq!(decoded.encode(&decoded.text).unwrap(), bytes);
+    }
+
+    #[test]
+    fn round_trips_utf16_with_a_byte_order_mark() {
+        let mut little = vec![0xFF, 0xFE];
+        little.extend(utf16le("héllo 😀\n"));
+        round_trip(&little, None, "héllo 😀\n");
+
+        let mut big = vec![0xFE, 0xFF];
+        big.extend("héllo\n".encode_utf16().flat_map(u16::to_be_bytes));
+        round_trip(&big, None, "héllo\n");
+    }
+
+    #[test]
+    fn reads_utf16_without_a_byte_order_mark_by_fallback() {
+        round_trip(&utf16le("plain\n"), Some(UTF_16LE), "plain\n");
+        // Without NUL bytes the file cannot be UTF-16 and is read as UTF-8
+        let decoded = decode(b"ascii".to_vec(), Some(UTF_16LE)).unwrap();
+        assert!(decoded.is_utf8());
+    }
+
+    #[test]
+    fn round_trips_legacy_encodings() {
+        round_trip(b"caf\xe9 \x80\n", Some(WINDOWS_1252), "café €\n");
+        let decoded = decode(b"caf\xe9\n".to_vec(), Some(WINDOWS_1252)).unwrap();
+        assert!(decoded.encode("snowman ☃").is_err());
+    }
+
+    #[test]
+    fn keeps_utf8_as_read() {
+        let decoded = decode("\u{FEFF}bom\n".as_bytes().to_vec(), None).unwrap();
+        assert_eq!(decoded.text, "\u{FEFF}bom\n");
+        assert!(decoded.is_utf8());
+    }
+
+    #[test]
+    fn rejects_invalid_bytes() {
+        assert!(decode(b"caf\xe9".to_vec(), None).is_err());
+        // A UTF-8 byte order mark does not fall back to another encoding
+        assert!(decode(b"\xef\xbb\xbfcaf\xe9".to_vec(), Some(WINDOWS_1252)).is_err());
+        // An unpaired surrogate is not valid UTF-16
+        assert!(decode(vec![0xFF, 0xFE, 0x00, 0xD8], None).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::config::GhostScrubConfig;
+use glob::Pattern;
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+// A compiled set of include/exclude glob rules. Rules are evaluated
+// gitignore-style: the last matching rule wins and a leading `!` negates it.
+pub struct PathFilter {
+    root: PathBuf,
+    cwd: PathBuf,
+    include: Vec<PatternRule>,
+    exclude: Vec<PatternRule>,
+    overrides: Vec<Vec<PatternRule>>,
+}
+
+struct PatternRule {
+    pattern: Pattern,
+    negated: bool,
+}
+
+impl PathFilter {
+    pub fn new(config: &GhostScrubConfig) -> Self {
+        Self {
+            root: config.project_root(),
+            cwd: env::current_dir().unwrap_or_default(),
+            include: compile_rules(&config.include_patterns),
+            exclude: compile_rules(&config.exclude_patterns),
+            overrides: config
+                .overrides
+                .iter()
+                .map(|override_config| compile_rules(&override_config.files))
+                .collect(),
+        }
+    }
+
+    pub fn is_file_included(&self, path: &Path) -> bool {
+        let relative = self.relative_path(path);
+        let included = self.include.is_empty() || last_match(&self.include, &relative);
+        included && !last_match(&self.exclude, &relative)
+    }
+
+    pub fn is_dir_excluded(&self, path: &Path) -> bool {
+        // Directory patterns such as `**/target/**` only match with a trailing separator
+        let relative = format!("{}/", self.relative_path(path));
+        last_match(&self.exclude, &relative)
+    }
+
+    // Indices of the config overrides whose `files` patterns match, in config order
+    pub fn matching_overrides(&self, path: &Path) -> Vec<usize> {
+        if self.overrides.is_empty() {
+            return Vec::new();
+        }
+
+        let relative = self.relative_path(path);
+        self.overrides
+            .iter()
+            .enumerate()
+            .filter(|(_, rules)| last_match(rules, &relative))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    fn relative_path(&self, path: &Path) -> String {
+        // The project root may be above the current directory, so relative
+        // paths are resolved before stripping it
+        let absolute = absolute_path(&self.cwd, path);
+        let path = absolute.strip_prefix(&self.root).unwrap_or(&absolute);
+
+        path.components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+// Joins `path` onto `cwd` and resolves `.` and `..` without touching the disk
+pub fn absolute_path(cwd: &Path, path: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+fn compile_rules(patterns: &[String]) -> Vec<PatternRule> {
+    patterns
+        .iter()
+        .filter_map(|pattern| {
+            let (negated, pattern) = match pattern.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, pattern.as_str()),
+            };
+            Pattern::new(pattern)
+                .ok()
+                .map(|pattern| PatternRule { pattern, negated })
+        })
+        .collect()
+}
+
+fn last_match(rules: &[PatternRule], path: &str) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.pattern.matches(path))
+        .is_some_and(|rule| !rule.negated)
+}
@@ -2,23 +2,41 @@ use clap::{Arg, Command};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
 
+mod charset;
+mod cleaner;
 mod config;
+mod directives;
+mod discovery;
+mod encoding;
+mod filter;
+mod markdown;
 mod processor;
+mod report;
+mod shebang;
 mod walker;
 mod watcher;
 
 use config::GhostScrubConfig;
-use walker::FileWalker;
+use report::OutputFormat;
+use walker::{FileWalker, WalkResult};
 use watcher::FileWatcher;
 
+// Exit codes used by --check so CI can tell dirty files apart from failures
+const EXIT_CHECK_DIRTY: i32 = 1;
+const EXIT_CHECK_ERROR: i32 = 2;
+
 #[derive(Debug)]
 struct CliConfig {
     paths: Vec<PathBuf>,
     dry_run: bool,
+    check: bool,
     watch: bool,
     config_file: Option<PathBuf>,
     verbose: bool,
+    output_format: OutputFormat,
+    jobs: usize,
 }
 
 fn main() {
@@ -37,6 +55,15 @@ fn main() {
                         .action(clap::ArgAction::SetTrue),
                 ),
         )
+        .subcommand(
+            Command::new("config")
+                .about("Inspect the configuration")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("validate")
+                        .about("Check configuration files for errors without processing any files"),
+                ),
+        )
         .arg(
             Arg::new("paths")
                 .help("Files or directories to process (defaults to current directory)")
@@ -51,6 +78,13 @@ fn main() {
                 .help("Show what would be changed without modifying files")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Report files that would change without modifying them and exit non-zero if any are found")
+                .conflicts_with("watch")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("watch")
                 .long("watch")
@@ -62,8 +96,9 @@ fn main() {
             Arg::new("config")
                 .long("config")
                 .short('c')
-                .help("Path to configuration file (defaults to .ghostscrub)")
+                .help("Path to configuration file (disables .ghostscrub discovery)")
                 .value_name("FILE")
+                .global(true)
                 .value_parser(clap::value_parser!(PathBuf)),
         )
         .arg(
@@ -73,6 +108,22 @@ fn main() {
                 .help("Show detailed output including diffs of changes")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .help("Number of files to process in parallel (defaults to the number of CPUs)")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format for results")
+                .value_name("FORMAT")
+                .value_parser(["text", "json", "jsonl", "sarif"])
+                .default_value("text"),
+        )
         .get_matches();
 
     // Handle init subcommand
@@ -85,48 +136,119 @@ fn main() {
         return;
     }
 
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        if config_matches.subcommand_matches("validate").is_some() {
+            let config_file = matches.get_one::<PathBuf>("config");
+            if let Err(e) = run_config_validate(config_file) {
+                eprintln!("Invalid configuration: {e}");
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let check = matches.get_flag("check");
+    let error_exit_code = if check { EXIT_CHECK_ERROR } else { 1 };
+
     let cli_config = CliConfig {
         paths: matches
             .get_many::<PathBuf>("paths")
             .map(|vals| vals.cloned().collect())
             .unwrap_or_else(|| vec![PathBuf::from(".")]),
-        dry_run: matches.get_flag("dry-run"),
+        dry_run: matches.get_flag("dry-run") || check,
+        check,
         watch: matches.get_flag("watch"),
         config_file: matches.get_one::<PathBuf>("config").cloned(),
         verbose: matches.get_flag("verbose"),
+        output_format: matches
+            .get_one::<String>("format")
+            .and_then(|name| OutputFormat::from_name(name))
+            .unwrap_or(OutputFormat::Text),
+        jobs: matches
+            .get_one::<usize>("jobs")
+            .copied()
+            .unwrap_or_else(|| {
+                thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            }),
     };
 
     // Load configuration
-    let ghost_config = if let Some(config_path) = &cli_config.config_file {
-        match GhostScrubConfig::load_from_file(config_path) {
-            Ok(config) => config,
-            Err(e) => {
-                eprintln!("Error loading config file {}: {}", config_path.display(), e);
-                process::exit(1);
-            }
+    let ghost_config = match load_config(cli_config.config_file.as_ref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config: {e}");
+            process::exit(error_exit_code);
         }
-    } else {
-        GhostScrubConfig::load_default()
     };
 
     if cli_config.watch {
+        if cli_config.output_format != OutputFormat::Text {
+            eprintln!("Watch mode only supports text output");
+            process::exit(1);
+        }
         if let Err(e) = run_watch_mode(&cli_config, ghost_config) {
             eprintln!("Watch mode error: {e}");
             process::exit(1);
         }
-    } else if let Err(e) = run_single_pass(&cli_config, ghost_config) {
-        eprintln!("Processing error: {e}");
-        process::exit(1);
+    } else {
+        match run_single_pass(&cli_config, ghost_config) {
+            Ok(result) if cli_config.check => {
+                if result.errors > 0 {
+                    process::exit(EXIT_CHECK_ERROR);
+                } else if result.files_changed > 0 {
+                    process::exit(EXIT_CHECK_DIRTY);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Processing error: {e}");
+                process::exit(error_exit_code);
+            }
+        }
     }
 }
 
 fn run_single_pass(
     cli_config: &CliConfig,
     ghost_config: GhostScrubConfig,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let walker = FileWalker::new(ghost_config);
+) -> Result<WalkResult, Box<dyn std::error::Error>> {
+    let project_root = ghost_config.project_root();
+    let walker = FileWalker::new(ghost_config, cli_config.output_format, cli_config.jobs);
     let result = walker.process_paths(&cli_config.paths, cli_config.dry_run, cli_config.verbose)?;
-    result.print_summary(cli_config.dry_run);
+    match cli_config.output_format {
+        OutputFormat::Text => result.print_summary(cli_config.dry_run),
+        OutputFormat::Json => report::print_json_report(&result, cli_config.dry_run)?,
+        OutputFormat::JsonLines => report::print_json_line_summary(&result, cli_config.dry_run)?,
+        OutputFormat::Sarif => {
+            report::print_sarif_report(&result, cli_config.dry_run, &project_root)?
+        }
+    }
+    Ok(result)
+}
+
+fn load_config(
+    config_file: Option<&PathBuf>,
+) -> Result<GhostScrubConfig, Box<dyn std::error::Error>> {
+    match config_file {
+        Some(config_path) => GhostScrubConfig::load_from_file(config_path),
+        None => GhostScrubConfig::load_default(),
+    }
+}
+
+fn run_config_validate(config_file: Option<&PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(config_file)?;
+
+    if config.sources.is_empty() {
+        println!("No .ghostscrub files found; using the default configuration.");
+        return Ok(());
+    }
+
+    println!("Configuration is valid:");
+    for source in &config.sources {
+        println!("  {}", source.display());
+    }
     Ok(())
 }
 
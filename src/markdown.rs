@@ -0,0 +1,263 @@
+use crate::cleaner::{CleanOutput, Cleaner};
+use crate::directives::Suppressions;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MARKDOWN_EXTENSIONS: [&str; 3] = ["md", "markdown", "mdx"];
+
+pub fn is_markdown_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| MARKDOWN_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+// A run of whole lines that is cleaned under one set of rules. Fence lines
+// themselves belong to the surrounding prose.
+struct Segment<'a> {
+    text: &'a str,
+    first_line: usize,
+    // The fence's info string language for code blocks, None for prose
+    language: Option<&'a str>,
+}
+
+struct Fence<'a> {
+    marker: char,
+    len: usize,
+    language: &'a str,
+}
+
+// Cleans prose with `prose` and each fenced code block with the cleaner
+// `code_cleaner` picks for the block's language.
+pub fn clean<'a>(
+    content: &'a str,
+    suppressed: &Suppressions,
+    prose: &Cleaner,
+    mut code_cleaner: impl FnMut(&str) -> Arc<Cleaner>,
+) -> CleanOutput<'a> {
+    let mut cleaned = String::with_capacity(content.len());
+    let mut edits = Vec::new();
+
+    for segment in segments(content) {
+        let output = match segment.language {
+            None => prose.clean_from(segment.text, segment.first_line, suppressed),
+            Some(language) => {
+                code_cleaner(language).clean_from(segment.text, segment.first_line, suppressed)
+            }
+        };
+        cleaned.push_str(&output.content);
+        edits.extend(output.edits);
+    }
+
+    let content = if edits.is_empty() {
+        Cow::Borrowed(content)
+    } else {
+        Cow::Owned(cleaned)
+    };
+    CleanOutput { content, edits }
+}
+
+// The path a code block would have as a file of its own, so overrides written
+// for that language's files also apply to it
+pub fn code_block_path(markdown_path: &Path, language: &str) -> PathBuf {
+    match language_extension(language) {
+        Some(extension) => markdown_path.with_extension(extension),
+        None => markdown_path.to_path_buf(),
+    }
+}
+
+fn language_extension(language: &str) -> Option<String> {
+    // Pandoc-style info strings such as `{.python}`
+    let language = language
+        .trim_start_matches('{')
+        .trim_start_matches('.')
+        .trim_end_matches('}')
+        .to_ascii_lowercase();
+
+    let extension = match language.as_str() {
+        "" => return None,
+        "rust" => "rs",
+        "python" | "python3" => "py",
+        "javascript" | "node" => "js",
+        "typescript" => "ts",
+        "golang" => "go",
+        "c++" | "cxx" => "cpp",
+        "csharp" | "c#" => "cs",
+        "ruby" => "rb",
+        "kotlin" => "kt",
+        "clojure" => "clj",
+        "haskell" => "hs",
+        "ocaml" => "ml",
+        "markdown" => "md",
+        "bash" | "shell" | "zsh" | "console" => "sh",
+        other => other,
+    };
+
+    extension
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+        .then(|| extension.to_string())
+}
+
+fn segments(content: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut fence: Option<Fence> = None;
+    let mut start = 0;
+    let mut start_line = 1;
+    let mut offset = 0;
+
+    for (idx, line) in content.split_inclusive('\n').enumerate() {
+        let end = offset + line.len();
+
+        match &fence {
+            None => {
+                if let Some(opening) = opening_fence(line) {
+                    push_segment(&mut segments, content, start..end, start_line, None);
+                    start = end;
+                    start_line = idx + 2;
+                    fence = Some(opening);
+                }
+            }
+            Some(opening) => {
+                if is_closing_fence(line, opening) {
+                    let language = Some(opening.language);
+                    push_segment(&mut segments, content, start..offset, start_line, language);
+                    start = offset;
+                    start_line = idx + 1;
+                    fence = None;
+                }
+            }
+        }
+        offset = end;
+    }
+
+    // An unclosed fence runs to the end of the document
+    let language = fence.map(|opening| opening.language);
+    push_segment(
+        &mut segments,
+        content,
+        start..content.len(),
+        start_line,
+        language,
+    );
+    segments
+}
+
+fn push_segment<'a>(
+    segments: &mut Vec<Segment<'a>>,
+    content: &'a str,
+    range: std::ops::Range<usize>,
+    first_line: usize,
+    language: Option<&'a str>,
+) {
+    if !range.is_empty() {
+        segments.push(Segment {
+            text: &content[range],
+            first_line,
+            language,
+        });
+    }
+}
+
+// Fences may be indented by up to three spaces
+fn strip_fence_indent(line: &str) -> Option<&str> {
+    let rest = line.trim_start_matches(' ');
+    (line.len() - rest.len() <= 3).then_some(rest)
+}
+
+fn opening_fence(line: &str) -> Option<Fence<'_>> {
+    let rest = strip_fence_indent(line)?;
+    let marker = rest.chars().next().filter(|&ch| ch == '`' || ch == '~')?;
+    let len = rest.len() - rest.trim_start_matches(marker).len();
+    if len < 3 {
+        return None;
+    }
+
+    let info = rest[len..].trim();
+    // Backtick fences cannot have backticks in their info string
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+
+    Some(Fence {
+        marker,
+        len,
+        language: info.split_whitespace().next().unwrap_or(""),
+    })
+}
+
+fn is_closing_fence(line: &str, opening: &Fence) -> bool {
+    let Some(rest) = strip_fence_indent(line) else {
+        return false;
+    };
+    let after = rest.trim_start_matches(opening.marker);
+    rest.len() - after.len() >= opening.len && after.trim().is_empty()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each segment as (first line, language, text)
+    fn split(content: &str) -> Vec<(usize, Option<&str>, &str)> {
+        segments(content)
+            .into_iter()
+            .map(|segment| (segment.first_line, segment.language, segment.text))
+            .collect()
+    }
+
+    #[test]
+    fn splits_fenced_code_from_prose() {
+        let content = "Intro\n```rust\nfn main() {}\n```\nOutro\n";
+        assert_eq!(
+            split(content),
+            [
+                (1, None, "Intro\n```rust\n"),
+                (3, Some("rust"), "fn main() {}\n"),
+                (4, None, "```\nOutro\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn closes_fences_with_a_matching_marker() {
+        // A shorter or different marker does not close the block
+        let content = "~~~~ python extra\n```\n~~~\nx\n~~~~~\n";
+        assert_eq!(
+            split(content),
+            [
+                (1, None, "~~~~ python extra\n"),
+                (2, Some("python"), "```\n~~~\nx\n"),
+                (5, None, "~~~~~\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_unclosed_fences_to_the_end() {
+        let content = "text\n   ```\ncode\n";
+        assert_eq!(
+            split(content),
+            [(1, None, "text\n   ```\n"), (3, Some(""), "code\n")]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fences() {
+        // Too short, indented four spaces, or backticks in the info string
+        for content in ["``\nx\n", "    ```\nx\n", "``` a`b\nx\n"] {
+            assert_eq!(split(content), [(1, None, content)]);
+        }
+    }
+
+    #[test]
+    fn maps_languages_to_file_paths() {
+        let path = Path::new("docs/README.md");
+        assert_eq!(code_block_path(path, "python"), Path::new("docs/README.py"));
+        assert_eq!(
+            code_block_path(path, "{.rust}"),
+            Path::new("docs/README.rs")
+        );
+        assert_eq!(code_block_path(path, ""), path);
+        assert_eq!(code_block_path(path, "c/c++"), path);
+    }
+}
@@ -1,14 +1,95 @@
-use crate::config::{GhostScrubConfig, VerbosityLevel};
+use crate::cleaner::{
+    bidi_control_name, is_emoji_flag_tags, is_tag_char, Category, CleanOutput, Cleaner, Finding,
+};
+use crate::config::{FileSettings, GhostScrubConfig, MarkdownMode, VerbosityLevel};
+use crate::directives::{self, Suppressions};
+use crate::encoding;
+use crate::filter::PathFilter;
+use crate::markdown;
+use crate::report::OutputFormat;
+use crate::shebang;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Everything needed to clean files matched by one combination of overrides
+struct FileRules {
+    settings: FileSettings,
+    cleaner: Arc<Cleaner>,
+    prose_cleaner: Cleaner,
+}
 
 pub struct FileProcessor {
     config: GhostScrubConfig,
+    filter: PathFilter,
+    // Built on first use, keyed by the indices of the matching overrides
+    rules: Mutex<HashMap<Vec<usize>, Arc<FileRules>>>,
+    output_format: OutputFormat,
 }
 
 impl FileProcessor {
-    pub fn new(config: GhostScrubConfig) -> Self {
-        Self { config }
+    pub fn new(config: GhostScrubConfig, output_format: OutputFormat) -> Self {
+        let filter = PathFilter::new(&config);
+        Self {
+            config,
+            filter,
+            rules: Mutex::new(HashMap::new()),
+            output_format,
+        }
+    }
+
+    pub fn settings_for(&self, file_path: &Path) -> FileSettings {
+        self.rules_for(file_path).settings.clone()
+    }
+
+    pub fn should_process(&self, file_path: &Path) -> bool {
+        let language_path = shebang::language_path(file_path);
+        self.rules_for(file_path)
+            .settings
+            .should_process_file(file_path, &language_path)
+    }
+
+    fn rules_for(&self, file_path: &Path) -> Arc<FileRules> {
+        let overrides = self.filter.matching_overrides(file_path);
+        let mut rules = self
+            .rules
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let file_rules = rules.entry(overrides).or_insert_with_key(|overrides| {
+            let settings = self.config.settings_for(overrides);
+            let targets = &settings.target_characters;
+            Arc::new(FileRules {
+                cleaner: Arc::new(Cleaner::new(targets, settings.line_endings, settings.bom())),
+                prose_cleaner: Cleaner::for_markdown_prose(
+                    targets,
+                    settings.line_endings,
+                    settings.bom(),
+                ),
+                settings,
+            })
+        });
+        Arc::clone(file_rules)
+    }
+
+    fn clean<'a>(
+        &self,
+        file_path: &Path,
+        rules: &FileRules,
+        content: &'a str,
+        suppressed: &Suppressions,
+    ) -> CleanOutput<'a> {
+        if rules.settings.markdown == MarkdownMode::Plain || !markdown::is_markdown_file(file_path)
+        {
+            return rules.cleaner.clean(content, suppressed);
+        }
+
+        markdown::clean(content, suppressed, &rules.prose_cleaner, |language| {
+            let code_path = markdown::code_block_path(file_path, language);
+            Arc::clone(&self.rules_for(&code_path).cleaner)
+        })
     }
 
     pub fn process_file(
@@ -16,177 +97,139 @@ impl FileProcessor {
         file_path: &Path,
         dry_run: bool,
         verbose: bool,
+        out: &mut String,
     ) -> Result<ProcessResult, Box<dyn std::error::Error>> {
-        if !self.config.should_process_file(file_path) {
-            return Ok(ProcessResult::Skipped);
+        // Overrides match the real path; extensionless scripts only take the
+        // extension filters and comment syntax of their language
+        let language_path = shebang::language_path(file_path);
+        let rules = self.rules_for(file_path);
+        if !rules
+            .settings
+            .should_process_file(file_path, &language_path)
+        {
+            return Ok(ProcessResult::Skipped(SkipReason::FileType));
+        }
+        let text_output = self.output_format == OutputFormat::Text;
+        let verbosity_verbose = matches!(rules.settings.verbosity, VerbosityLevel::Verbose);
+
+        let max_file_size = rules.settings.max_file_size;
+        if max_file_size > 0 && fs::metadata(file_path)?.len() > max_file_size {
+            if text_output && verbosity_verbose {
+                writeln!(
+                    out,
+                    "Skipped ({}): {}",
+                    SkipReason::TooLarge.description(),
+                    file_path.display()
+                )?;
+            }
+            return Ok(ProcessResult::Skipped(SkipReason::TooLarge));
+        }
+
+        let bytes = fs::read(file_path)?;
+        if encoding::looks_binary(&bytes, rules.settings.fallback_encoding) {
+            if text_output && verbosity_verbose {
+                writeln!(
+                    out,
+                    "Skipped ({}): {}",
+                    SkipReason::Binary.description(),
+                    file_path.display()
+                )?;
+            }
+            return Ok(ProcessResult::Skipped(SkipReason::Binary));
         }
 
-        let content = fs::read_to_string(file_path)?;
-        let cleaned_content = self.clean_content(&content);
+        let decoded = encoding::decode(bytes, rules.settings.fallback_encoding)?;
+        let content = &decoded.text;
+        let suppressed = directives::find_suppressions(&language_path, content);
+        let mut cleaned = self.clean(&language_path, &rules, content, &suppressed);
+        if !decoded.is_utf8() {
+            cleaned
+                .edits
+                .retain(|edit| edit.category != Category::ByteOrderMark);
+        }
 
-        if content == cleaned_content {
-            if matches!(self.config.verbosity, VerbosityLevel::Verbose) {
-                println!("No changes needed: {}", file_path.display());
+        if cleaned.edits.is_empty() {
+            if text_output && verbosity_verbose {
+                writeln!(out, "No changes needed: {}", file_path.display())?;
             }
             return Ok(ProcessResult::NoChanges);
         }
 
-        let changes_count = self.count_changes(&content, &cleaned_content);
+        let report = FileReport {
+            path: file_path.to_path_buf(),
+            changes: cleaned.edits.len(),
+            findings: cleaned.edits,
+            hidden_messages: find_hidden_messages(&rules.cleaner, content, &suppressed),
+        };
 
-        if verbose {
-            self.print_diff(file_path, &content, &cleaned_content, dry_run);
+        if verbose && text_output {
+            self.write_diff(out, &report, content, &cleaned.content, dry_run)?;
         }
 
         if dry_run {
-            if !verbose {
-                println!(
+            if !verbose && text_output {
+                writeln!(
+                    out,
                     "Would clean {} invisible characters from: {}",
-                    changes_count,
+                    report.changes,
                     file_path.display()
-                );
+                )?;
+                self.write_hidden_messages(out, &report.hidden_messages)?;
             }
-            Ok(ProcessResult::DryRun(changes_count))
+            Ok(ProcessResult::DryRun(report))
         } else {
-            fs::write(file_path, cleaned_content)?;
-            if !matches!(self.config.verbosity, VerbosityLevel::Silent) && !verbose {
-                println!(
+            fs::write(file_path, decoded.encode(&cleaned.content)?)?;
+            if !matches!(rules.settings.verbosity, VerbosityLevel::Silent)
+                && !verbose
+                && text_output
+            {
+                writeln!(
+                    out,
                     "Cleaned {} invisible characters from: {}",
-                    changes_count,
+                    report.changes,
                     file_path.display()
-                );
+                )?;
+                self.write_hidden_messages(out, &report.hidden_messages)?;
             }
-            Ok(ProcessResult::Cleaned(changes_count))
+            Ok(ProcessResult::Cleaned(report))
         }
     }
 
-    fn clean_content(&self, content: &str) -> String {
-        let mut result = content.to_string();
-
-        if self.config.target_characters.zero_width_spaces {
-            result = self.remove_zero_width_spaces(&result);
-        }
-
-        if self.config.target_characters.non_breaking_spaces {
-            result = self.remove_non_breaking_spaces(&result);
+    fn write_hidden_messages(&self, out: &mut String, messages: &[HiddenMessage]) -> fmt::Result {
+        for message in messages {
+            writeln!(
+                out,
+                "  Hidden tag message on line {}: {:?}",
+                message.line, message.text
+            )?;
         }
-
-        if self.config.target_characters.control_characters {
-            result = self.remove_control_characters(&result);
-        }
-
-        if self.config.target_characters.unicode_whitespace {
-            result = self.remove_unicode_whitespace(&result);
-        }
-
-        if self.config.target_characters.trailing_whitespace {
-            result = self.remove_trailing_whitespace(&result);
-        }
-
-        for custom_char in &self.config.target_characters.custom_chars {
-            if let Ok(unicode_char) = u32::from_str_radix(custom_char.trim_start_matches("U+"), 16)
-            {
-                if let Some(ch) = char::from_u32(unicode_char) {
-                    result = result.replace(ch, "");
-                }
-            }
-        }
-
-        // Remove lines that contain only whitespace (spaces, tabs)
-        result = self.remove_whitespace_only_lines(&result);
-
-        result
-    }
-
-    fn remove_zero_width_spaces(&self, content: &str) -> String {
-        content.replace(['\u{200B}', '\u{200C}', '\u{200D}', '\u{FEFF}'], "") // Zero Width No-Break Space (BOM)
+        Ok(())
     }
 
-    fn remove_non_breaking_spaces(&self, content: &str) -> String {
-        content.replace('\u{00A0}', " ") // Non-Breaking Space -> regular space
-    }
-
-    fn remove_control_characters(&self, content: &str) -> String {
-        content
-            .chars()
-            .filter(|&ch| {
-                // Keep newlines, carriage returns, and tabs
-                if ch == '\n' || ch == '\r' || ch == '\t' {
-                    return true;
-                }
-                // Remove other ASCII control characters
-                !(ch as u32 <= 0x1F || ch as u32 == 0x7F)
-            })
-            .collect()
-    }
-
-    fn remove_unicode_whitespace(&self, content: &str) -> String {
-        content
-            .chars()
-            .filter(|&ch| {
-                // Keep normal spaces, newlines, carriage returns, and tabs
-                if ch == ' ' || ch == '\n' || ch == '\r' || ch == '\t' {
-                    return true;
-                }
-                // Remove other Unicode whitespace characters
-                !ch.is_whitespace()
-            })
-            .collect()
-    }
-
-    fn remove_trailing_whitespace(&self, content: &str) -> String {
-        let has_final_newline = content.ends_with('\n');
-        let result = content
-            .lines()
-            .map(|line| line.trim_end())
-            .collect::<Vec<&str>>()
-            .join("\n");
-
-        if has_final_newline {
-            format!("{result}\n")
-        } else {
-            result
-        }
-    }
-
-    fn remove_whitespace_only_lines(&self, content: &str) -> String {
-        let has_final_newline = content.ends_with('\n');
-        let result = content
-            .lines()
-            .map(|line| {
-                if line.trim().is_empty() {
-                    // Keep the newline but remove all whitespace
-                    ""
-                } else {
-                    line
-                }
-            })
-            .collect::<Vec<&str>>()
-            .join("\n");
-
-        if has_final_newline {
-            format!("{result}\n")
-        } else {
-            result
-        }
-    }
-
-    fn print_diff(&self, file_path: &Path, original: &str, cleaned: &str, dry_run: bool) {
+    fn write_diff(
+        &self,
+        out: &mut String,
+        report: &FileReport,
+        original: &str,
+        cleaned: &str,
+        dry_run: bool,
+    ) -> fmt::Result {
         let action = if dry_run { "Would clean" } else { "Cleaned" };
-        let changes_count = self.count_changes(original, cleaned);
 
-        println!(
+        writeln!(
+            out,
             "{} {} invisible characters from: {}",
             action,
-            changes_count,
-            file_path.display()
-        );
+            report.changes,
+            report.path.display()
+        )?;
 
-        if changes_count == 0 {
-            return;
+        if report.changes == 0 {
+            return Ok(());
         }
 
-        println!("--- Original");
-        println!("+++ Cleaned");
+        writeln!(out, "--- Original")?;
+        writeln!(out, "+++ Cleaned")?;
 
         let original_lines: Vec<&str> = original.lines().collect();
         let cleaned_lines: Vec<&str> = cleaned.lines().collect();
@@ -198,11 +241,28 @@ impl FileProcessor {
             let clean_line = cleaned_lines.get(i).unwrap_or(&"");
 
             if orig_line != clean_line {
-                println!("-{}: {}", i + 1, self.visualize_invisible_chars(orig_line));
-                println!("+{}: {}", i + 1, self.visualize_invisible_chars(clean_line));
+                writeln!(
+                    out,
+                    "-{}: {}",
+                    i + 1,
+                    self.visualize_invisible_chars(orig_line)
+                )?;
+                writeln!(
+                    out,
+                    "+{}: {}",
+                    i + 1,
+                    self.visualize_invisible_chars(clean_line)
+                )?;
             }
         }
-        println!();
+
+        let (original_endings, cleaned_endings) =
+            (line_ending_style(original), line_ending_style(cleaned));
+        if original_endings != cleaned_endings {
+            writeln!(out, "Line endings: {original_endings} -> {cleaned_endings}")?;
+        }
+        self.write_hidden_messages(out, &report.hidden_messages)?;
+        writeln!(out)
     }
 
     fn visualize_invisible_chars(&self, text: &str) -> String {
@@ -225,15 +285,32 @@ impl FileProcessor {
             "⦃EMPTY⦄".to_string()
         } else {
             // Check for trailing whitespace
-            let has_trailing_whitespace = text.len() != text.trim_end().len();
-            let main_content = text
-                .chars()
-                .map(|ch| match ch {
+            let trimmed = text.trim_end();
+            let has_trailing_whitespace = text.len() != trimmed.len();
+            let mut main_content = String::new();
+            let mut chars = trimmed.char_indices().peekable();
+            while let Some((start, ch)) = chars.next() {
+                if is_tag_char(ch) {
+                    let mut end = start + ch.len_utf8();
+                    while let Some((idx, next)) = chars.next_if(|&(_, next)| is_tag_char(next)) {
+                        end = idx + next.len_utf8();
+                    }
+                    main_content.push_str(&format!(
+                        "⦃TAGS:{:?}⦄",
+                        decode_tag_run(&trimmed[start..end])
+                    ));
+                    continue;
+                }
+
+                main_content.push_str(&match ch {
                     '\u{200B}' => "⦃ZWS⦄".to_string(),
                     '\u{200C}' => "⦃ZWNJ⦄".to_string(),
                     '\u{200D}' => "⦃ZWJ⦄".to_string(),
                     '\u{FEFF}' => "⦃BOM⦄".to_string(),
                     '\u{00A0}' => "⦃NBSP⦄".to_string(),
+                    ch if bidi_control_name(ch).is_some() => {
+                        format!("⦃{}⦄", bidi_control_name(ch).unwrap_or_default())
+                    }
                     '\t' => "⦃TAB⦄".to_string(),
                     ' ' => " ".to_string(), // Keep regular spaces visible
                     ch if ch.is_control() && ch != '\n' && ch != '\r' => {
@@ -248,13 +325,12 @@ impl FileProcessor {
                         format!("⦃WS:U+{:04X}⦄", ch as u32)
                     }
                     ch => ch.to_string(),
-                })
-                .collect::<String>();
+                });
+            }
 
             if has_trailing_whitespace {
-                let trailing_chars: String = text
+                let trailing_chars: String = text[trimmed.len()..]
                     .chars()
-                    .skip(text.trim_end().len())
                     .map(|ch| match ch {
                         ' ' => "SP".to_string(),
                         '\t' => "TAB".to_string(),
@@ -264,22 +340,197 @@ impl FileProcessor {
                     })
                     .collect::<Vec<_>>()
                     .join("+");
-                format!("{}⦃TRAILING: {}⦄", text.trim_end(), trailing_chars)
+                format!("{main_content}⦃TRAILING: {trailing_chars}⦄")
             } else {
                 main_content
             }
         }
     }
+}
+
+fn find_hidden_messages(
+    cleaner: &Cleaner,
+    content: &str,
+    suppressed: &Suppressions,
+) -> Vec<HiddenMessage> {
+    if !cleaner.strips_tag_characters() {
+        return Vec::new();
+    }
+
+    tag_runs(content)
+        .into_iter()
+        .filter(|run| !run.is_emoji_flag)
+        .map(|run| {
+            let (line, column) = line_and_column(content, run.start);
+            HiddenMessage {
+                line,
+                column,
+                text: decode_tag_run(&content[run.start..run.end]),
+            }
+        })
+        .filter(|message| {
+            !(cleaner.directives_suppress_tag_characters() && suppressed.contains(message.line))
+        })
+        .collect()
+}
+
+fn line_ending_style(content: &str) -> &'static str {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+
+    match (crlf, lf) {
+        (0, 0) => "none",
+        (0, _) => "LF",
+        (_, 0) => "CRLF",
+        _ => "mixed",
+    }
+}
+
+// Tag characters mirror ASCII at an offset of 0xE0000; anything outside the
+// printable range (LANGUAGE TAG, CANCEL TAG) carries no text.
+fn decode_tag_run(run: &str) -> String {
+    run.chars()
+        .filter_map(|ch| char::from_u32(ch as u32 - 0xE0000))
+        .filter(|ch| (' '..='~').contains(ch))
+        .collect()
+}
+
+fn tag_runs(content: &str) -> Vec<TagRun> {
+    let mut runs = Vec::new();
+    let mut chars = content.char_indices().peekable();
+    let mut previous = None;
+
+    while let Some((start, ch)) = chars.next() {
+        if !is_tag_char(ch) {
+            previous = Some(ch);
+            continue;
+        }
 
-    fn count_changes(&self, original: &str, cleaned: &str) -> usize {
-        original.len() - cleaned.len()
+        let mut end = start + ch.len_utf8();
+        while let Some((idx, next)) = chars.next_if(|&(_, next)| is_tag_char(next)) {
+            end = idx + next.len_utf8();
+        }
+
+        let tags = &content[start..end];
+        runs.push(TagRun {
+            start,
+            end,
+            is_emoji_flag: is_emoji_flag_tags(previous, tags.chars()),
+        });
+        previous = tags.chars().next_back();
     }
+
+    runs
+}
+
+struct TagRun {
+    start: usize,
+    end: usize,
+    is_emoji_flag: bool,
+}
+
+fn line_and_column(content: &str, byte_offset: usize) -> (usize, usize) {
+    let before = &content[..byte_offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HiddenMessage {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub changes: usize,
+    pub findings: Vec<Finding>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hidden_messages: Vec<HiddenMessage>,
 }
 
 #[derive(Debug)]
 pub enum ProcessResult {
-    Cleaned(usize),
-    DryRun(usize),
+    Cleaned(FileReport),
+    DryRun(FileReport),
     NoChanges,
-    Skipped,
+    Skipped(SkipReason),
+}
+
+// Why a file was left alone without being cleaned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    FileType,
+    TooLarge,
+    Binary,
+}
+
+impl SkipReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            SkipReason::FileType => "excluded by file type",
+            SkipReason::TooLarge => "larger than max_file_size",
+            SkipReason::Binary => "binary content",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ghost-scrub-processor-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn process(path: &Path) -> ProcessResult {
+        let config = GhostScrubConfig {
+            root_dir: path.parent().map(Path::to_path_buf),
+            ..GhostScrubConfig::default()
+        };
+        FileProcessor::new(config, OutputFormat::Json)
+            .process_file(path, true, false, &mut String::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn skips_binaries_with_text_names() {
+        let dir = scratch_dir("binary");
+        for name in ["LICENSE", "data.txt", "main.rs"] {
+            let path = dir.join(name);
+            fs::write(&path, b"\x7fELF\x02\x01\x01\0\0\0\x03\0>\0\x01\0").unwrap();
+            assert!(
+                matches!(process(&path), ProcessResult::Skipped(SkipReason::Binary)),
+                "{name}"
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cleans_a_stray_nul_in_source() {
+        let dir = scratch_dir("nul");
+        let path = dir.join("main.rs");
+        let source = format!("let s = \"a\0b\";\n{}", "// padding\n".repeat(4));
+        fs::write(&path, source).unwrap();
+        match process(&path) {
+            ProcessResult::DryRun(report) => {
+                assert_eq!(report.findings[0].category, Category::ControlCharacter);
+            }
+            _ => panic!("expected the NUL to be reported"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,353 @@
+use crate::cleaner::{format_codepoint, serialize_codepoint, Action, Category};
+use crate::filter::absolute_path;
+use crate::processor::{FileReport, SkipReason};
+use crate::walker::WalkResult;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+// The SARIF base URI that results are relative to
+const SRCROOT: &str = "%SRCROOT%";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    JsonLines,
+    Sarif,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "jsonl" => Some(OutputFormat::JsonLines),
+            "sarif" => Some(OutputFormat::Sarif),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Summary {
+    dry_run: bool,
+    files_processed: usize,
+    files_changed: usize,
+    files_skipped: usize,
+    skipped_by_reason: BTreeMap<SkipReason, usize>,
+    total_changes: usize,
+    errors: usize,
+    by_category: BTreeMap<Category, usize>,
+    top_codepoints: Vec<CodepointCount>,
+    top_files: Vec<FileCount>,
+}
+
+#[derive(Serialize)]
+struct CodepointCount {
+    #[serde(serialize_with = "serialize_codepoint")]
+    codepoint: char,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct FileCount {
+    path: PathBuf,
+    changes: usize,
+}
+
+impl Summary {
+    fn from_result(result: &WalkResult, dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            files_processed: result.files_processed,
+            files_changed: result.files_changed,
+            files_skipped: result.files_skipped,
+            skipped_by_reason: result.skipped_by_reason.clone(),
+            total_changes: result.total_changes,
+            errors: result.errors,
+            by_category: result.by_category.clone(),
+            top_codepoints: result
+                .top_codepoints()
+                .into_iter()
+                .map(|(codepoint, count)| CodepointCount { codepoint, count })
+                .collect(),
+            top_files: result
+                .top_files()
+                .into_iter()
+                .map(|(path, changes)| FileCount {
+                    path: path.to_path_buf(),
+                    changes,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    files: &'a [FileReport],
+    summary: Summary,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonLine<'a> {
+    File(&'a FileReport),
+    Summary(Summary),
+}
+
+pub fn print_json_report(result: &WalkResult, dry_run: bool) -> Result<(), serde_json::Error> {
+    let report = JsonReport {
+        files: &result.reports,
+        summary: Summary::from_result(result, dry_run),
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+pub fn print_json_line_file(report: &FileReport) -> Result<(), serde_json::Error> {
+    println!("{}", serde_json::to_string(&JsonLine::File(report))?);
+    Ok(())
+}
+
+pub fn print_json_line_summary(
+    result: &WalkResult,
+    dry_run: bool,
+) -> Result<(), serde_json::Error> {
+    let summary = JsonLine::Summary(Summary::from_result(result, dry_run));
+    println!("{}", serde_json::to_string(&summary)?);
+    Ok(())
+}
+
+pub fn print_sarif_report(
+    result: &WalkResult,
+    dry_run: bool,
+    project_root: &Path,
+) -> Result<(), serde_json::Error> {
+    let cwd = env::current_dir().unwrap_or_default();
+    let rules: Vec<Value> = Category::ALL
+        .iter()
+        .map(|category| {
+            json!({
+                "id": category.id(),
+                "shortDescription": { "text": category.description() },
+                "defaultConfiguration": { "level": sarif_level(*category) },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = result
+        .reports
+        .iter()
+        .flat_map(|report| {
+            let location = artifact_location(&cwd, project_root, report);
+            let finding_location = location.clone();
+            let findings = report.findings.iter().map(move |finding| {
+                let region = json!({
+                    "startLine": finding.line,
+                    "startColumn": finding.column,
+                    "endLine": finding.line,
+                    "endColumn": finding.column + 1,
+                });
+                let codepoint = format_codepoint(finding.codepoint);
+                let mut deleted = region.clone();
+                let (fix, inserted) = match (finding.action, finding.replacement) {
+                    (Action::Replaced, Some(replacement)) => (
+                        format!(
+                            "Replace {} with {}",
+                            codepoint,
+                            format_codepoint(replacement)
+                        ),
+                        replacement.to_string(),
+                    ),
+                    (Action::Inserted, _) => {
+                        // An insertion deletes nothing, so its region is empty
+                        deleted["endColumn"] = json!(finding.column);
+                        (format!("Insert {codepoint}"), finding.codepoint.to_string())
+                    }
+                    _ => (format!("Remove {codepoint}"), String::new()),
+                };
+
+                json!({
+                    "ruleId": finding.category.id(),
+                    "level": sarif_level(finding.category),
+                    "message": {
+                        "text": format!("{}: {}", finding.category.description(), codepoint),
+                    },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": finding_location,
+                            "region": region,
+                        },
+                    }],
+                    "fixes": [{
+                        "description": {
+                            "text": fix,
+                        },
+                        "artifactChanges": [{
+                            "artifactLocation": finding_location,
+                            "replacements": [{
+                                "deletedRegion": deleted,
+                                "insertedContent": { "text": inserted },
+                            }],
+                        }],
+                    }],
+                })
+            });
+
+            // The tags of a hidden message are findings already; this result
+            // carries the text they decode to
+            let hidden_messages = report.hidden_messages.iter().map(move |message| {
+                json!({
+                    "ruleId": Category::TagCharacter.id(),
+                    "level": sarif_level(Category::TagCharacter),
+                    "message": {
+                        "text": format!("Hidden tag message: {:?}", message.text),
+                    },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": location,
+                            "region": {
+                                "startLine": message.line,
+                                "startColumn": message.column,
+                            },
+                        },
+                    }],
+                    "properties": {
+                        "decodedText": message.text,
+                    },
+                })
+            });
+            findings.chain(hidden_messages)
+        })
+        .collect();
+
+    let sarif = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ghost-scrub",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/jmvrbanac/ghost-scrub",
+                    "rules": rules,
+                },
+            },
+            "originalUriBaseIds": {
+                SRCROOT: { "uri": directory_uri(project_root) },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+            "properties": {
+                "summary": Summary::from_result(result, dry_run),
+            },
+        }],
+    });
+
+    println!("{}", serde_json::to_string_pretty(&sarif)?);
+    Ok(())
+}
+
+fn sarif_level(category: Category) -> &'static str {
+    match category {
+        Category::TagCharacter | Category::BidiControl => "error",
+        _ => "warning",
+    }
+}
+
+// Files under the project root are addressed relative to it, so results
+// line up with the repository wherever the tool was run from
+fn artifact_location(cwd: &Path, project_root: &Path, report: &FileReport) -> Value {
+    let absolute = absolute_path(cwd, &report.path);
+    match absolute.strip_prefix(project_root) {
+        Ok(relative) => json!({
+            "uri": encode_uri_path(relative),
+            "uriBaseId": SRCROOT,
+        }),
+        Err(_) => json!({ "uri": format!("file://{}", encode_uri_path(&absolute)) }),
+    }
+}
+
+fn directory_uri(path: &Path) -> String {
+    let uri = format!("file://{}", encode_uri_path(path));
+    if uri.ends_with('/') {
+        uri
+    } else {
+        format!("{uri}/")
+    }
+}
+
+// Joins path components with `/` and percent-encodes anything a URI path
+// cannot hold as is
+fn encode_uri_path(path: &Path) -> String {
+    let mut uri = String::new();
+    for component in path.components() {
+        let part = match component {
+            Component::RootDir => continue,
+            component => component.as_os_str().to_string_lossy(),
+        };
+        // Absolute paths start with a slash, Windows ones after the drive
+        if !uri.is_empty() || path.has_root() {
+            uri.push('/');
+        }
+        for byte in part.bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&byte) {
+                uri.push(byte as char);
+            } else {
+                uri.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(path: &str) -> FileReport {
+        FileReport {
+            path: PathBuf::from(path),
+            changes: 0,
+            findings: Vec::new(),
+            hidden_messages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn locates_files_relative_to_the_project_root() {
+        let root = Path::new("/work/repo");
+        let cwd = Path::new("/work/repo/src");
+        assert_eq!(
+            artifact_location(cwd, root, &report("./a.txt")),
+            json!({ "uri": "src/a.txt", "uriBaseId": SRCROOT })
+        );
+        assert_eq!(
+            artifact_location(cwd, root, &report("../docs/read me.md")),
+            json!({ "uri": "docs/read%20me.md", "uriBaseId": SRCROOT })
+        );
+    }
+
+    #[test]
+    fn uses_absolute_uris_outside_the_project_root() {
+        let root = Path::new("/work/repo");
+        assert_eq!(
+            artifact_location(root, root, &report("/tmp/a.txt")),
+            json!({ "uri": "file:///tmp/a.txt" })
+        );
+        assert_eq!(directory_uri(root), "file:///work/repo/");
+    }
+
+    #[test]
+    fn names_categories_by_their_rule_ids() {
+        let by_category = BTreeMap::from([(Category::ByteOrderMark, 1), (Category::Custom, 2)]);
+        assert_eq!(
+            serde_json::to_value(by_category).unwrap(),
+            json!({ "custom-character": 2, "byte-order-mark": 1 })
+        );
+    }
+}
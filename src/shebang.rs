@@ -0,0 +1,95 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Long enough for any reasonable interpreter line
+const SHEBANG_READ_LEN: usize = 256;
+
+// The path a file is classified by: its own, or for an extensionless script
+// the same path with the extension of the language its shebang names, so
+// extension filters, overrides and comment syntax treat it like that language
+pub fn language_path(file_path: &Path) -> PathBuf {
+    if file_path.extension().is_some() {
+        return file_path.to_path_buf();
+    }
+    match read_shebang(file_path)
+        .as_deref()
+        .and_then(interpreter_extension)
+    {
+        Some(extension) => file_path.with_extension(extension),
+        None => file_path.to_path_buf(),
+    }
+}
+
+fn read_shebang(file_path: &Path) -> Option<String> {
+    let mut buf = [0; SHEBANG_READ_LEN];
+    let mut file = File::open(file_path).ok()?;
+    let len = file.read(&mut buf).ok()?;
+
+    let line = buf[..len].split(|&byte| byte == b'\n').next()?;
+    let line = line.strip_prefix(b"#!")?;
+    Some(String::from_utf8_lossy(line).into_owned())
+}
+
+// Maps `/bin/sh`, `/usr/bin/env python3` or `/usr/bin/env -S node --flag` to
+// the extension of the interpreter's language
+fn interpreter_extension(shebang: &str) -> Option<&'static str> {
+    let mut words = shebang.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // Skip env's own options and variable assignments
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+
+    // Versioned interpreters such as python3.12 or ruby2.7
+    let name = program.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '.');
+    let extension = match name {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" | "mksh" => "sh",
+        "python" | "pypy" => "py",
+        "node" | "nodejs" | "deno" | "bun" => "js",
+        "ts-node" | "tsx" => "ts",
+        "ruby" => "rb",
+        "perl" => "pl",
+        "php" => "php",
+        "pwsh" | "powershell" => "ps1",
+        "lua" => "lua",
+        "runhaskell" | "runghc" => "hs",
+        "ocaml" => "ml",
+        "scala" => "scala",
+        _ => return None,
+    };
+    Some(extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_interpreters_to_extensions() {
+        assert_eq!(interpreter_extension("/bin/sh"), Some("sh"));
+        assert_eq!(interpreter_extension("/usr/bin/env bash"), Some("sh"));
+        assert_eq!(interpreter_extension(" /usr/bin/python3.12 -u"), Some("py"));
+        assert_eq!(interpreter_extension("/usr/bin/env ruby2.7"), Some("rb"));
+        assert_eq!(interpreter_extension("/usr/local/bin/node"), Some("js"));
+    }
+
+    #[test]
+    fn skips_env_options_and_assignments() {
+        assert_eq!(
+            interpreter_extension("/usr/bin/env -S node --experimental-modules"),
+            Some("js")
+        );
+        assert_eq!(
+            interpreter_extension("/usr/bin/env -i PATH=/bin perl -w"),
+            Some("pl")
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_interpreters() {
+        assert_eq!(interpreter_extension("/usr/bin/env"), None);
+        assert_eq!(interpreter_extension("/usr/bin/make -f"), None);
+        assert_eq!(interpreter_extension(""), None);
+    }
+}
@@ -1,18 +1,48 @@
+use crate::cleaner::{format_codepoint, Category};
 use crate::config::GhostScrubConfig;
-use crate::processor::{FileProcessor, ProcessResult};
-use glob::{glob, Pattern};
-use std::fs;
+use crate::filter::PathFilter;
+use crate::processor::{FileProcessor, FileReport, ProcessResult, SkipReason};
+use crate::report::{self, OutputFormat};
+use glob::glob;
+use ignore::WalkBuilder;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+pub const IGNORE_FILE_NAME: &str = ".ghostscrubignore";
+// How many codepoints and files the summary lists before cutting off
+pub const TOP_ENTRIES: usize = 10;
 
 pub struct FileWalker {
     processor: FileProcessor,
     config: GhostScrubConfig,
+    filter: Arc<PathFilter>,
+    output_format: OutputFormat,
+    jobs: usize,
+}
+
+// Everything a worker produces for one file, replayed in input order so the
+// printed output does not depend on thread scheduling.
+struct FileOutcome {
+    path: PathBuf,
+    output: String,
+    result: Result<ProcessResult, String>,
 }
 
 impl FileWalker {
-    pub fn new(config: GhostScrubConfig) -> Self {
-        let processor = FileProcessor::new(config.clone());
-        Self { processor, config }
+    pub fn new(config: GhostScrubConfig, output_format: OutputFormat, jobs: usize) -> Self {
+        let processor = FileProcessor::new(config.clone(), output_format);
+        let filter = Arc::new(PathFilter::new(&config));
+        Self {
+            processor,
+            config,
+            filter,
+            output_format,
+            jobs: jobs.max(1),
+        }
     }
 
     pub fn process_paths(
@@ -22,102 +52,186 @@ impl FileWalker {
         verbose: bool,
     ) -> Result<WalkResult, Box<dyn std::error::Error>> {
         let mut result = WalkResult::default();
+        let mut files = Vec::new();
 
         for path in paths {
             if path.is_file() {
-                self.process_single_file(path, dry_run, verbose, &mut result)?;
+                files.push(path.clone());
             } else if path.is_dir() {
-                self.process_directory(path, dry_run, verbose, &mut result)?;
+                self.collect_directory(path, &mut files, &mut result);
             } else {
                 // Handle as glob pattern
-                self.process_glob_pattern(&path.to_string_lossy(), dry_run, verbose, &mut result)?;
+                self.collect_glob_pattern(&path.to_string_lossy(), &mut files, &mut result)?;
             }
         }
 
+        self.process_files(&files, dry_run, verbose, &mut result)?;
         Ok(result)
     }
 
-    fn process_single_file(
+    fn process_files(
         &self,
-        file_path: &Path,
+        files: &[PathBuf],
         dry_run: bool,
         verbose: bool,
         result: &mut WalkResult,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        match self.processor.process_file(file_path, dry_run, verbose) {
-            Ok(ProcessResult::Cleaned(count)) => {
-                result.files_processed += 1;
-                result.total_changes += count;
+        let jobs = self.jobs.min(files.len());
+
+        if jobs <= 1 {
+            for path in files {
+                let outcome = self.process_single_file(path, dry_run, verbose);
+                result.merge(self.record_outcome(outcome)?);
+            }
+            return Ok(());
+        }
+
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let (tx, rx) = mpsc::channel();
+
+            for _ in 0..jobs {
+                let tx = tx.clone();
+                let next = &next;
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = files.get(index) else {
+                        break;
+                    };
+                    let outcome = self.process_single_file(path, dry_run, verbose);
+                    if tx.send((index, outcome)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+
+            // Workers finish out of order; hold results back until every
+            // earlier file has been reported.
+            let mut pending = BTreeMap::new();
+            let mut next_to_record = 0;
+            for (index, outcome) in rx {
+                pending.insert(index, outcome);
+                while let Some(outcome) = pending.remove(&next_to_record) {
+                    result.merge(self.record_outcome(outcome)?);
+                    next_to_record += 1;
+                }
             }
-            Ok(ProcessResult::DryRun(count)) => {
+            Ok(())
+        })
+    }
+
+    fn process_single_file(&self, file_path: &Path, dry_run: bool, verbose: bool) -> FileOutcome {
+        let mut output = String::new();
+        let result = self
+            .processor
+            .process_file(file_path, dry_run, verbose, &mut output)
+            .map_err(|e| e.to_string());
+
+        FileOutcome {
+            path: file_path.to_path_buf(),
+            output,
+            result,
+        }
+    }
+
+    fn record_outcome(
+        &self,
+        outcome: FileOutcome,
+    ) -> Result<WalkResult, Box<dyn std::error::Error>> {
+        let mut result = WalkResult::default();
+        print!("{}", outcome.output);
+
+        match outcome.result {
+            Ok(ProcessResult::Cleaned(report)) | Ok(ProcessResult::DryRun(report)) => {
                 result.files_processed += 1;
-                result.total_changes += count;
+                result.files_changed += 1;
+                result.total_changes += report.changes;
+                result.record_findings(&report);
+                self.record_report(report, &mut result)?;
             }
             Ok(ProcessResult::NoChanges) => {
                 result.files_processed += 1;
             }
-            Ok(ProcessResult::Skipped) => {
+            Ok(ProcessResult::Skipped(reason)) => {
                 result.files_skipped += 1;
+                *result.skipped_by_reason.entry(reason).or_default() += 1;
             }
             Err(e) => {
-                eprintln!("Error processing {}: {}", file_path.display(), e);
+                eprintln!("Error processing {}: {}", outcome.path.display(), e);
                 result.errors += 1;
             }
         }
-        Ok(())
+        Ok(result)
     }
 
-    fn process_directory(
+    fn record_report(
         &self,
-        dir_path: &Path,
-        dry_run: bool,
-        verbose: bool,
+        report: FileReport,
         result: &mut WalkResult,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        self.walk_directory_recursive(dir_path, dry_run, verbose, result)?;
+        match self.output_format {
+            OutputFormat::Text => {}
+            OutputFormat::Json | OutputFormat::Sarif => result.reports.push(report),
+            OutputFormat::JsonLines => report::print_json_line_file(&report)?,
+        }
         Ok(())
     }
 
-    fn walk_directory_recursive(
+    fn collect_directory(
         &self,
         dir_path: &Path,
-        dry_run: bool,
-        verbose: bool,
+        files: &mut Vec<PathBuf>,
         result: &mut WalkResult,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let entries = fs::read_dir(dir_path)?;
+    ) {
+        let filter = Arc::clone(&self.filter);
+        let respect_gitignore = self.config.respect_gitignore;
 
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
+        let walker = WalkBuilder::new(dir_path)
+            .hidden(false)
+            .ignore(respect_gitignore)
+            .git_ignore(respect_gitignore)
+            .git_global(respect_gitignore)
+            .git_exclude(respect_gitignore)
+            .require_git(false)
+            .add_custom_ignore_filename(IGNORE_FILE_NAME)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |entry| {
+                let is_dir = entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                !(is_dir && filter.is_dir_excluded(entry.path()))
+            })
+            .build();
 
-            if path.is_dir() {
-                if self.should_skip_path(&path) {
-                    continue;
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if entry.path().is_file() && self.filter.is_file_included(entry.path()) {
+                        files.push(entry.into_path());
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Walk error: {e}");
+                    result.errors += 1;
                 }
-                self.walk_directory_recursive(&path, dry_run, verbose, result)?;
-            } else if path.is_file() && !self.should_skip_path(&path) {
-                self.process_single_file(&path, dry_run, verbose, result)?;
             }
         }
-
-        Ok(())
     }
 
-    fn process_glob_pattern(
+    fn collect_glob_pattern(
         &self,
         pattern: &str,
-        dry_run: bool,
-        verbose: bool,
+        files: &mut Vec<PathBuf>,
         result: &mut WalkResult,
     ) -> Result<(), Box<dyn std::error::Error>> {
         for entry in glob(pattern)? {
             match entry {
                 Ok(path) => {
                     if path.is_file() {
-                        self.process_single_file(&path, dry_run, verbose, result)?;
+                        files.push(path);
                     } else if path.is_dir() {
-                        self.process_directory(&path, dry_run, verbose, result)?;
+                        self.collect_directory(&path, files, result);
                     }
                 }
                 Err(e) => {
@@ -128,36 +242,80 @@ impl FileWalker {
         }
         Ok(())
     }
-
-    fn should_skip_path(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-
-        // Check against exclude patterns
-        for pattern_str in &self.config.exclude_patterns {
-            if let Ok(pattern) = Pattern::new(pattern_str) {
-                if pattern.matches(&path_str) {
-                    return true;
-                }
-            }
-        }
-
-        false
-    }
 }
 
 #[derive(Debug, Default)]
 pub struct WalkResult {
     pub files_processed: usize,
+    pub files_changed: usize,
     pub files_skipped: usize,
     pub total_changes: usize,
     pub errors: usize,
+    pub skipped_by_reason: BTreeMap<SkipReason, usize>,
+    pub by_category: BTreeMap<Category, usize>,
+    pub by_codepoint: BTreeMap<char, usize>,
+    pub changed_files: Vec<(PathBuf, usize)>,
+    pub reports: Vec<FileReport>,
 }
 
 impl WalkResult {
+    pub fn merge(&mut self, other: WalkResult) {
+        self.files_processed += other.files_processed;
+        self.files_changed += other.files_changed;
+        self.files_skipped += other.files_skipped;
+        self.total_changes += other.total_changes;
+        self.errors += other.errors;
+        for (reason, count) in other.skipped_by_reason {
+            *self.skipped_by_reason.entry(reason).or_default() += count;
+        }
+        for (category, count) in other.by_category {
+            *self.by_category.entry(category).or_default() += count;
+        }
+        for (codepoint, count) in other.by_codepoint {
+            *self.by_codepoint.entry(codepoint).or_default() += count;
+        }
+        self.changed_files.extend(other.changed_files);
+        self.reports.extend(other.reports);
+    }
+
+    fn record_findings(&mut self, report: &FileReport) {
+        for finding in &report.findings {
+            *self.by_category.entry(finding.category).or_default() += 1;
+            *self.by_codepoint.entry(finding.codepoint).or_default() += 1;
+        }
+        self.changed_files
+            .push((report.path.clone(), report.changes));
+    }
+
+    // Most frequent first; ties keep codepoint order
+    pub fn top_codepoints(&self) -> Vec<(char, usize)> {
+        let mut codepoints: Vec<(char, usize)> = self
+            .by_codepoint
+            .iter()
+            .map(|(&ch, &count)| (ch, count))
+            .collect();
+        codepoints.sort_by_key(|&(_, count)| Reverse(count));
+        codepoints.truncate(TOP_ENTRIES);
+        codepoints
+    }
+
+    // Most changes first; ties keep the order files were processed in
+    pub fn top_files(&self) -> Vec<(&Path, usize)> {
+        let mut files: Vec<(&Path, usize)> = self
+            .changed_files
+            .iter()
+            .map(|(path, changes)| (path.as_path(), *changes))
+            .collect();
+        files.sort_by_key(|&(_, changes)| Reverse(changes));
+        files.truncate(TOP_ENTRIES);
+        files
+    }
+
     pub fn print_summary(&self, dry_run: bool) {
         if dry_run {
             println!("\nDry run summary:");
             println!("  Files that would be processed: {}", self.files_processed);
+            println!("  Files that would be changed: {}", self.files_changed);
             println!(
                 "  Invisible characters that would be removed: {}",
                 self.total_changes
@@ -165,15 +323,38 @@ impl WalkResult {
         } else {
             println!("\nProcessing summary:");
             println!("  Files processed: {}", self.files_processed);
+            println!("  Files changed: {}", self.files_changed);
             println!("  Invisible characters removed: {}", self.total_changes);
         }
 
         if self.files_skipped > 0 {
             println!("  Files skipped: {}", self.files_skipped);
+            for (reason, count) in &self.skipped_by_reason {
+                println!("    {}: {}", reason.description(), count);
+            }
         }
 
         if self.errors > 0 {
             println!("  Errors encountered: {}", self.errors);
         }
+
+        if self.total_changes == 0 {
+            return;
+        }
+
+        println!("\nChanges by category:");
+        for (category, count) in &self.by_category {
+            println!("  {}: {}", category.id(), count);
+        }
+
+        println!("\nMost frequent characters:");
+        for (codepoint, count) in self.top_codepoints() {
+            println!("  {}: {}", format_codepoint(codepoint), count);
+        }
+
+        println!("\nFiles with the most changes:");
+        for (path, changes) in self.top_files() {
+            println!("  {}: {}", path.display(), changes);
+        }
     }
 }
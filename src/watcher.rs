@@ -1,6 +1,7 @@
-use crate::config::GhostScrubConfig;
+use crate::config::{GhostScrubConfig, VerbosityLevel};
+use crate::filter::PathFilter;
 use crate::processor::{FileProcessor, ProcessResult};
-use glob::Pattern;
+use crate::report::OutputFormat;
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
@@ -8,13 +9,14 @@ use std::time::Duration;
 
 pub struct FileWatcher {
     processor: FileProcessor,
-    config: GhostScrubConfig,
+    filter: PathFilter,
 }
 
 impl FileWatcher {
     pub fn new(config: GhostScrubConfig) -> Self {
-        let processor = FileProcessor::new(config.clone());
-        Self { processor, config }
+        let filter = PathFilter::new(&config);
+        let processor = FileProcessor::new(config, OutputFormat::Text);
+        Self { processor, filter }
     }
 
     pub fn watch_paths(&self, paths: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
@@ -64,18 +66,28 @@ impl FileWatcher {
             EventKind::Create(_) | EventKind::Modify(_) => {
                 for path in event.paths {
                     if path.is_file() && self.should_process_file(&path) {
-                        match self.processor.process_file(&path, false, false) {
-                            Ok(ProcessResult::Cleaned(count)) => {
+                        let mut output = String::new();
+                        let result = self
+                            .processor
+                            .process_file(&path, false, false, &mut output);
+                        print!("{output}");
+                        match result {
+                            Ok(ProcessResult::Cleaned(report))
+                                if !matches!(
+                                    self.processor.settings_for(&path).verbosity,
+                                    VerbosityLevel::Silent
+                                ) =>
+                            {
                                 println!(
                                     "Auto-cleaned {} invisible characters from: {}",
-                                    count,
+                                    report.changes,
                                     path.display()
                                 );
                             }
-                            Ok(ProcessResult::NoChanges) => {
-                                // Silent for no changes in watch mode
+                            Ok(ProcessResult::Cleaned(_)) | Ok(ProcessResult::NoChanges) => {
+                                // Silent for no changes, or for files configured to be silent
                             }
-                            Ok(ProcessResult::Skipped) => {
+                            Ok(ProcessResult::Skipped(_)) => {
                                 // Silent for skipped files
                             }
                             Ok(ProcessResult::DryRun(_)) => {
@@ -96,15 +108,9 @@ impl FileWatcher {
     }
 
     fn should_process_file(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-
-        // Check against exclude patterns from config
-        for pattern_str in &self.config.exclude_patterns {
-            if let Ok(pattern) = Pattern::new(pattern_str) {
-                if pattern.matches(&path_str) {
-                    return false;
-                }
-            }
+        // Check against include and exclude patterns from config
+        if !self.filter.is_file_included(path) {
+            return false;
         }
 
         // Skip temporary files, swap files, and hidden files commonly created by editors
@@ -119,7 +125,7 @@ impl FileWatcher {
             }
         }
 
-        // Use config's file filtering logic
-        self.config.should_process_file(path)
+        // Use the file's resolved extension and file name filters
+        self.processor.should_process(path)
     }
 }